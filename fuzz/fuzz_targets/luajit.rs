@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// the signature is fixed so that the fuzzer explores the luajit parser only
+fuzz_target!(|data: &[u8]| {
+    let _ = luac_parser::parse(&[&b"\x1bLJ"[..], data].concat());
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// luau has no signature, it is detected by the version bytes which are left to the fuzzer
+fuzz_target!(|data: &[u8]| {
+    let _ = luac_parser::parse(data);
+});
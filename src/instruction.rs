@@ -0,0 +1,719 @@
+//! Version independent view of the decoded instructions
+
+use super::*;
+
+/// A set of consecutive registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Registers {
+    /// Registers in `start..end`
+    Range(u16, u16),
+    /// Registers from `start` up to the stack top, e.g. multiple results of a call
+    Open(u16),
+}
+
+/// A constant referenced by an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConstantRef {
+    /// Index of `LuaChunk::constants`
+    Constant(u32),
+    /// Index of `LuaChunk::num_constants`, for luajit
+    Number(u32),
+    /// Index of `LuaChunk::prototypes`
+    Proto(u32),
+}
+
+/// Common questions which can be asked to an instruction of any format
+pub trait Instruction {
+    fn name(&self) -> &'static str;
+
+    /// Whether the instruction calls a function value, including tail calls and iterator calls
+    fn is_call(&self) -> bool;
+
+    /// Whether the instruction returns from the function
+    fn is_return(&self) -> bool;
+
+    /// Registers written by the instruction
+    fn writes(&self) -> Vec<Registers>;
+
+    /// Targets of the instruction located at `pc` besides the next instruction,
+    /// pcs are the indices of `LuaChunk::instructions`
+    fn jump_targets(&self, pc: usize) -> Vec<usize>;
+
+    /// Whether the execution may continue with the next instruction
+    fn falls_through(&self) -> bool;
+
+    /// Constants referenced by the instruction
+    fn constants(&self) -> Vec<ConstantRef>;
+}
+
+/// Instruction of any format, dispatched from the header version by [`decode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnyInstruction {
+    Lua51(lua51::Instruction),
+    Lua52(lua52::Instruction),
+    Lua53(lua53::Instruction),
+    Lua54(lua54::Instruction),
+    LuaJit(luajit::Instruction),
+    Luau(luau::Instruction),
+}
+
+/// Decode all instructions of a chunk into `(pc, instruction)` pairs according to the header version
+pub fn decode(
+    header: &LuaHeader,
+    chunk: &LuaChunk,
+) -> Result<Vec<(usize, AnyInstruction)>, DecodeError> {
+    fn wrap<I>(
+        code: Result<Vec<(usize, I)>, DecodeError>,
+        f: impl Fn(I) -> AnyInstruction,
+    ) -> Result<Vec<(usize, AnyInstruction)>, DecodeError> {
+        Ok(code?.into_iter().map(|(pc, i)| (pc, f(i))).collect())
+    }
+
+    match header.version() {
+        LUA51 => wrap(lua51::decode(chunk), AnyInstruction::Lua51),
+        LUA52 => wrap(lua52::decode(chunk), AnyInstruction::Lua52),
+        LUA53 => wrap(lua53::decode(chunk), AnyInstruction::Lua53),
+        LUA54 => wrap(lua54::decode(chunk), AnyInstruction::Lua54),
+        LUAJ1 | LUAJ2 => wrap(luajit::decode(header, chunk), AnyInstruction::LuaJit),
+        LUAU => wrap(
+            luau::decode(chunk, header.format_version),
+            AnyInstruction::Luau,
+        ),
+        v => Err(DecodeError::UnsupportedVersion { version: v.0 }),
+    }
+}
+
+macro_rules! dispatch {
+    ($self:ident, $i:ident => $e:expr) => {
+        match $self {
+            AnyInstruction::Lua51($i) => $e,
+            AnyInstruction::Lua52($i) => $e,
+            AnyInstruction::Lua53($i) => $e,
+            AnyInstruction::Lua54($i) => $e,
+            AnyInstruction::LuaJit($i) => $e,
+            AnyInstruction::Luau($i) => $e,
+        }
+    };
+}
+
+impl Instruction for AnyInstruction {
+    fn name(&self) -> &'static str {
+        dispatch!(self, i => Instruction::name(i))
+    }
+
+    fn is_call(&self) -> bool {
+        dispatch!(self, i => i.is_call())
+    }
+
+    fn is_return(&self) -> bool {
+        dispatch!(self, i => i.is_return())
+    }
+
+    fn writes(&self) -> Vec<Registers> {
+        dispatch!(self, i => i.writes())
+    }
+
+    fn jump_targets(&self, pc: usize) -> Vec<usize> {
+        dispatch!(self, i => i.jump_targets(pc))
+    }
+
+    fn falls_through(&self) -> bool {
+        dispatch!(self, i => i.falls_through())
+    }
+
+    fn constants(&self) -> Vec<ConstantRef> {
+        dispatch!(self, i => i.constants())
+    }
+}
+
+fn regs(start: impl Into<u16>, count: impl Into<u16>) -> Registers {
+    let start = start.into();
+    Registers::Range(start, start + count.into())
+}
+
+fn reg(r: impl Into<u16>) -> Registers {
+    regs(r, 1u16)
+}
+
+/// Registers of a variable count operand, where 0 means up to the top and n means n - 1 registers
+fn multi(start: impl Into<u16>, n: impl Into<u16>) -> Vec<Registers> {
+    let (start, n) = (start.into(), n.into());
+    match n {
+        0 => vec![Registers::Open(start)],
+        1 => vec![],
+        n => vec![regs(start, n - 1)],
+    }
+}
+
+fn relative(pc: usize, offset: i64) -> Vec<usize> {
+    usize::try_from(pc as i64 + 1 + offset)
+        .ok()
+        .into_iter()
+        .collect()
+}
+
+fn rk(rk: lua51::RK) -> Option<ConstantRef> {
+    match rk {
+        lua51::RK::Constant(k) => Some(ConstantRef::Constant(k as _)),
+        lua51::RK::Register(_) => None,
+    }
+}
+
+impl Instruction for lua51::Instruction {
+    fn name(&self) -> &'static str {
+        self.opcode().name()
+    }
+
+    fn is_call(&self) -> bool {
+        use lua51::Instruction::*;
+        matches!(self, Call { .. } | TailCall { .. } | TForLoop { .. })
+    }
+
+    fn is_return(&self) -> bool {
+        matches!(self, Self::Return { .. })
+    }
+
+    fn writes(&self) -> Vec<Registers> {
+        use lua51::Instruction::*;
+
+        match *self {
+            Move { a, .. }
+            | LoadK { a, .. }
+            | LoadBool { a, .. }
+            | GetUpval { a, .. }
+            | GetGlobal { a, .. }
+            | GetTable { a, .. }
+            | NewTable { a, .. }
+            | Add { a, .. }
+            | Sub { a, .. }
+            | Mul { a, .. }
+            | Div { a, .. }
+            | Mod { a, .. }
+            | Pow { a, .. }
+            | Unm { a, .. }
+            | Not { a, .. }
+            | Len { a, .. }
+            | Concat { a, .. }
+            | TestSet { a, .. }
+            | ForPrep { a, .. }
+            | Closure { a, .. } => vec![reg(a)],
+            LoadNil { a, b } => vec![Registers::Range(a as _, b + 1)],
+            Self_ { a, .. } => vec![regs(a, 2u16)],
+            Call { a, c, .. } => multi(a, c),
+            ForLoop { a, .. } => vec![reg(a), reg(a as u16 + 3)],
+            TForLoop { a, c } => vec![regs(a as u16 + 2, c + 1)],
+            VarArg { a, b } => multi(a, b),
+            _ => vec![],
+        }
+    }
+
+    fn jump_targets(&self, pc: usize) -> Vec<usize> {
+        use lua51::Instruction::*;
+
+        match *self {
+            Jmp { sbx } | ForLoop { sbx, .. } | ForPrep { sbx, .. } => relative(pc, sbx as _),
+            LoadBool { c, .. } if c != 0 => vec![pc + 2],
+            Eq { .. } | Lt { .. } | Le { .. } | Test { .. } | TestSet { .. } | TForLoop { .. } => {
+                vec![pc + 2]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn falls_through(&self) -> bool {
+        !matches!(
+            self,
+            Self::Jmp { .. } | Self::Return { .. } | Self::LoadBool { c: 1.., .. }
+        )
+    }
+
+    fn constants(&self) -> Vec<ConstantRef> {
+        use lua51::Instruction::*;
+
+        match *self {
+            LoadK { bx, .. } | GetGlobal { bx, .. } | SetGlobal { bx, .. } => {
+                vec![ConstantRef::Constant(bx)]
+            }
+            Closure { bx, .. } => vec![ConstantRef::Proto(bx)],
+            GetTable { c, .. } | Self_ { c, .. } => rk(c).into_iter().collect(),
+            SetTable { b, c, .. }
+            | Add { b, c, .. }
+            | Sub { b, c, .. }
+            | Mul { b, c, .. }
+            | Div { b, c, .. }
+            | Mod { b, c, .. }
+            | Pow { b, c, .. }
+            | Eq { b, c, .. }
+            | Lt { b, c, .. }
+            | Le { b, c, .. } => rk(b).into_iter().chain(rk(c)).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+impl Instruction for lua52::Instruction {
+    fn name(&self) -> &'static str {
+        self.opcode().name()
+    }
+
+    fn is_call(&self) -> bool {
+        use lua52::Instruction::*;
+        matches!(self, Call { .. } | TailCall { .. } | TForCall { .. })
+    }
+
+    fn is_return(&self) -> bool {
+        matches!(self, Self::Return { .. })
+    }
+
+    fn writes(&self) -> Vec<Registers> {
+        use lua52::Instruction::*;
+
+        match *self {
+            Move { a, .. }
+            | LoadK { a, .. }
+            | LoadKX { a, .. }
+            | LoadBool { a, .. }
+            | GetUpval { a, .. }
+            | GetTabUp { a, .. }
+            | GetTable { a, .. }
+            | NewTable { a, .. }
+            | Add { a, .. }
+            | Sub { a, .. }
+            | Mul { a, .. }
+            | Div { a, .. }
+            | Mod { a, .. }
+            | Pow { a, .. }
+            | Unm { a, .. }
+            | Not { a, .. }
+            | Len { a, .. }
+            | Concat { a, .. }
+            | TestSet { a, .. }
+            | ForPrep { a, .. }
+            | TForLoop { a, .. }
+            | Closure { a, .. } => vec![reg(a)],
+            LoadNil { a, b } => vec![regs(a, b + 1)],
+            Self_ { a, .. } => vec![regs(a, 2u16)],
+            Call { a, c, .. } => multi(a, c),
+            ForLoop { a, .. } => vec![reg(a), reg(a as u16 + 3)],
+            TForCall { a, c } => vec![regs(a as u16 + 3, c)],
+            VarArg { a, b } => multi(a, b),
+            _ => vec![],
+        }
+    }
+
+    fn jump_targets(&self, pc: usize) -> Vec<usize> {
+        use lua52::Instruction::*;
+
+        match *self {
+            Jmp { sbx, .. } | ForLoop { sbx, .. } | ForPrep { sbx, .. } | TForLoop { sbx, .. } => {
+                relative(pc, sbx as _)
+            }
+            LoadBool { c, .. } if c != 0 => vec![pc + 2],
+            Eq { .. } | Lt { .. } | Le { .. } | Test { .. } | TestSet { .. } => vec![pc + 2],
+            _ => vec![],
+        }
+    }
+
+    fn falls_through(&self) -> bool {
+        !matches!(
+            self,
+            Self::Jmp { .. } | Self::Return { .. } | Self::LoadBool { c: 1.., .. }
+        )
+    }
+
+    fn constants(&self) -> Vec<ConstantRef> {
+        use lua52::Instruction::*;
+
+        match *self {
+            LoadK { bx, .. } => vec![ConstantRef::Constant(bx)],
+            LoadKX { ax, .. } => vec![ConstantRef::Constant(ax)],
+            Closure { bx, .. } => vec![ConstantRef::Proto(bx)],
+            GetTabUp { c, .. } | GetTable { c, .. } | Self_ { c, .. } => {
+                rk(c).into_iter().collect()
+            }
+            SetTabUp { b, c, .. }
+            | SetTable { b, c, .. }
+            | Add { b, c, .. }
+            | Sub { b, c, .. }
+            | Mul { b, c, .. }
+            | Div { b, c, .. }
+            | Mod { b, c, .. }
+            | Pow { b, c, .. }
+            | Eq { b, c, .. }
+            | Lt { b, c, .. }
+            | Le { b, c, .. } => rk(b).into_iter().chain(rk(c)).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+impl Instruction for lua53::Instruction {
+    fn name(&self) -> &'static str {
+        self.opcode().name()
+    }
+
+    fn is_call(&self) -> bool {
+        use lua53::Instruction::*;
+        matches!(self, Call { .. } | TailCall { .. } | TForCall { .. })
+    }
+
+    fn is_return(&self) -> bool {
+        matches!(self, Self::Return { .. })
+    }
+
+    fn writes(&self) -> Vec<Registers> {
+        use lua53::Instruction::*;
+
+        match *self {
+            Move { a, .. }
+            | LoadK { a, .. }
+            | LoadKX { a, .. }
+            | LoadBool { a, .. }
+            | GetUpval { a, .. }
+            | GetTabUp { a, .. }
+            | GetTable { a, .. }
+            | NewTable { a, .. }
+            | Add { a, .. }
+            | Sub { a, .. }
+            | Mul { a, .. }
+            | Mod { a, .. }
+            | Pow { a, .. }
+            | Div { a, .. }
+            | IDiv { a, .. }
+            | BAnd { a, .. }
+            | BOr { a, .. }
+            | BXor { a, .. }
+            | Shl { a, .. }
+            | Shr { a, .. }
+            | Unm { a, .. }
+            | BNot { a, .. }
+            | Not { a, .. }
+            | Len { a, .. }
+            | Concat { a, .. }
+            | TestSet { a, .. }
+            | ForPrep { a, .. }
+            | TForLoop { a, .. }
+            | Closure { a, .. } => vec![reg(a)],
+            LoadNil { a, b } => vec![regs(a, b + 1)],
+            Self_ { a, .. } => vec![regs(a, 2u16)],
+            Call { a, c, .. } => multi(a, c),
+            ForLoop { a, .. } => vec![reg(a), reg(a as u16 + 3)],
+            TForCall { a, c } => vec![regs(a as u16 + 3, c)],
+            VarArg { a, b } => multi(a, b),
+            _ => vec![],
+        }
+    }
+
+    fn jump_targets(&self, pc: usize) -> Vec<usize> {
+        use lua53::Instruction::*;
+
+        match *self {
+            Jmp { sbx, .. } | ForLoop { sbx, .. } | ForPrep { sbx, .. } | TForLoop { sbx, .. } => {
+                relative(pc, sbx as _)
+            }
+            LoadBool { c, .. } if c != 0 => vec![pc + 2],
+            Eq { .. } | Lt { .. } | Le { .. } | Test { .. } | TestSet { .. } => vec![pc + 2],
+            _ => vec![],
+        }
+    }
+
+    fn falls_through(&self) -> bool {
+        !matches!(
+            self,
+            Self::Jmp { .. } | Self::Return { .. } | Self::LoadBool { c: 1.., .. }
+        )
+    }
+
+    fn constants(&self) -> Vec<ConstantRef> {
+        use lua53::Instruction::*;
+
+        match *self {
+            LoadK { bx, .. } => vec![ConstantRef::Constant(bx)],
+            LoadKX { ax, .. } => vec![ConstantRef::Constant(ax)],
+            Closure { bx, .. } => vec![ConstantRef::Proto(bx)],
+            GetTabUp { c, .. } | GetTable { c, .. } | Self_ { c, .. } => {
+                rk(c).into_iter().collect()
+            }
+            SetTabUp { b, c, .. }
+            | SetTable { b, c, .. }
+            | Add { b, c, .. }
+            | Sub { b, c, .. }
+            | Mul { b, c, .. }
+            | Mod { b, c, .. }
+            | Pow { b, c, .. }
+            | Div { b, c, .. }
+            | IDiv { b, c, .. }
+            | BAnd { b, c, .. }
+            | BOr { b, c, .. }
+            | BXor { b, c, .. }
+            | Shl { b, c, .. }
+            | Shr { b, c, .. }
+            | Eq { b, c, .. }
+            | Lt { b, c, .. }
+            | Le { b, c, .. } => rk(b).into_iter().chain(rk(c)).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+impl Instruction for lua54::Instruction {
+    fn name(&self) -> &'static str {
+        self.opcode().name()
+    }
+
+    fn is_call(&self) -> bool {
+        use lua54::Instruction::*;
+        matches!(self, Call { .. } | TailCall { .. } | TForCall { .. })
+    }
+
+    fn is_return(&self) -> bool {
+        use lua54::Instruction::*;
+        matches!(self, Return { .. } | Return0 | Return1 { .. })
+    }
+
+    fn writes(&self) -> Vec<Registers> {
+        use lua54::Instruction::*;
+
+        match *self {
+            Move { a, .. }
+            | LoadI { a, .. }
+            | LoadF { a, .. }
+            | LoadK { a, .. }
+            | LoadKX { a, .. }
+            | LoadFalse { a }
+            | LFalseSkip { a }
+            | LoadTrue { a }
+            | GetUpval { a, .. }
+            | GetTabUp { a, .. }
+            | GetTable { a, .. }
+            | GetI { a, .. }
+            | GetField { a, .. }
+            | NewTable { a, .. }
+            | AddI { a, .. }
+            | AddK { a, .. }
+            | SubK { a, .. }
+            | MulK { a, .. }
+            | ModK { a, .. }
+            | PowK { a, .. }
+            | DivK { a, .. }
+            | IDivK { a, .. }
+            | BAndK { a, .. }
+            | BOrK { a, .. }
+            | BXorK { a, .. }
+            | ShrI { a, .. }
+            | ShlI { a, .. }
+            | Add { a, .. }
+            | Sub { a, .. }
+            | Mul { a, .. }
+            | Mod { a, .. }
+            | Pow { a, .. }
+            | Div { a, .. }
+            | IDiv { a, .. }
+            | BAnd { a, .. }
+            | BOr { a, .. }
+            | BXor { a, .. }
+            | Shl { a, .. }
+            | Shr { a, .. }
+            | Unm { a, .. }
+            | BNot { a, .. }
+            | Not { a, .. }
+            | Len { a, .. }
+            | Concat { a, .. }
+            | TestSet { a, .. }
+            | Closure { a, .. } => vec![reg(a)],
+            LoadNil { a, b } => vec![regs(a, b as u16 + 1)],
+            Self_ { a, .. } => vec![regs(a, 2u8)],
+            Call { a, c, .. } => multi(a, c),
+            ForLoop { a, .. } => vec![regs(a, 2u8), reg(a as u16 + 3)],
+            ForPrep { a, .. } => vec![regs(a, 4u8)],
+            TForCall { a, c } => vec![regs(a as u16 + 4, c)],
+            TForLoop { a, .. } => vec![reg(a as u16 + 2)],
+            VarArg { a, c } => multi(a, c),
+            _ => vec![],
+        }
+    }
+
+    fn jump_targets(&self, pc: usize) -> Vec<usize> {
+        if let Some(target) = self.jump_target(pc) {
+            vec![target]
+        } else if self.is_test() || matches!(self, Self::LFalseSkip { .. }) {
+            vec![pc + 2]
+        } else {
+            vec![]
+        }
+    }
+
+    fn falls_through(&self) -> bool {
+        use lua54::Instruction::*;
+        !matches!(
+            self,
+            Jmp { .. }
+                | LFalseSkip { .. }
+                | TForPrep { .. }
+                | TailCall { .. }
+                | Return { .. }
+                | Return0
+                | Return1 { .. }
+        )
+    }
+
+    fn constants(&self) -> Vec<ConstantRef> {
+        use lua54::Instruction::*;
+
+        let k = |k: u8| ConstantRef::Constant(k as _);
+        match *self {
+            LoadK { bx, .. } => vec![ConstantRef::Constant(bx)],
+            LoadKX { ax, .. } => vec![ConstantRef::Constant(ax)],
+            Closure { bx, .. } => vec![ConstantRef::Proto(bx)],
+            GetTabUp { c, .. }
+            | GetField { c, .. }
+            | AddK { c, .. }
+            | SubK { c, .. }
+            | MulK { c, .. }
+            | ModK { c, .. }
+            | PowK { c, .. }
+            | DivK { c, .. }
+            | IDivK { c, .. }
+            | BAndK { c, .. }
+            | BOrK { c, .. }
+            | BXorK { c, .. } => vec![k(c)],
+            SetTabUp { b, c, .. } | SetField { b, c, .. } => {
+                [k(b)].into_iter().chain(rk(c)).collect()
+            }
+            SetTable { c, .. } | SetI { c, .. } | Self_ { c, .. } => rk(c).into_iter().collect(),
+            MmBinK { b, .. } | EqK { b, .. } => vec![k(b)],
+            _ => vec![],
+        }
+    }
+}
+
+impl Instruction for luajit::Instruction {
+    fn name(&self) -> &'static str {
+        self.op.name()
+    }
+
+    fn is_call(&self) -> bool {
+        use luajit::OpCode::*;
+        matches!(self.op, CallM | Call | CallMT | CallT | IterC | IterN)
+    }
+
+    fn is_return(&self) -> bool {
+        use luajit::OpCode::*;
+        matches!(self.op, RetM | Ret | Ret0 | Ret1)
+    }
+
+    fn writes(&self) -> Vec<Registers> {
+        use luajit::{OpCode::*, OperandMode};
+
+        let a = self.a;
+        match self.op {
+            KNil => vec![Registers::Range(a as _, self.d.saturating_add(1))],
+            CallM | Call => multi(a, self.b()),
+            IterC | IterN => vec![regs(a, (self.b().max(1) as u16 - 1).max(3))],
+            VArg => multi(a, self.b()),
+            ForI => vec![regs(a, 4u8)],
+            ForL | IForL | JForL => vec![reg(a), reg(a as u16 + 3)],
+            IterL | IIterL | JIterL => vec![reg(a.saturating_sub(1))],
+            op if op.a_mode() == OperandMode::Dst => vec![reg(a)],
+            _ => vec![],
+        }
+    }
+
+    fn jump_targets(&self, pc: usize) -> Vec<usize> {
+        use luajit::OpCode::*;
+
+        match self.op {
+            IsLt | IsGe | IsLe | IsGt | IsEqV | IsNeV | IsEqS | IsNeS | IsEqN | IsNeN | IsEqP
+            | IsNeP | IsTC | IsFC | IsT | IsF => vec![pc + 2],
+            // the jump of loop headers is only used by the jit compiler
+            Loop | ILoop | JLoop => vec![],
+            _ => self.jump_target(pc).into_iter().collect(),
+        }
+    }
+
+    fn falls_through(&self) -> bool {
+        use luajit::OpCode::*;
+        !matches!(
+            self.op,
+            Jmp | UClo | IsNext | CallMT | CallT | RetM | Ret | Ret0 | Ret1
+        )
+    }
+
+    fn constants(&self) -> Vec<ConstantRef> {
+        use luajit::OperandMode;
+
+        let mode = self.op.cd_mode();
+        if mode.is_gc_constant() {
+            vec![ConstantRef::Constant(self.cd() as _)]
+        } else if mode == OperandMode::Num {
+            vec![ConstantRef::Number(self.cd() as _)]
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl Instruction for luau::Instruction {
+    fn name(&self) -> &'static str {
+        self.op.name()
+    }
+
+    fn is_call(&self) -> bool {
+        self.op == luau::OpCode::Call
+    }
+
+    fn is_return(&self) -> bool {
+        self.op == luau::OpCode::Return
+    }
+
+    fn writes(&self) -> Vec<Registers> {
+        use luau::OpCode::*;
+
+        let a = self.a();
+        match self.op {
+            LoadNil | LoadB | LoadN | LoadK | Move | GetGlobal | GetUpval | GetImport
+            | GetTable | GetTableKS | GetTableN | NewClosure | Add | Sub | Mul | Div | Mod
+            | Pow | AddK | SubK | MulK | DivK | ModK | PowK | And | Or | AndK | OrK | Concat
+            | Not | Minus | Length | NewTable | DupTable | DupClosure | LoadKX | SubRK | DivRK
+            | IDiv | IDivK => vec![reg(a)],
+            NameCall => vec![regs(a, 2u8)],
+            Call => multi(a, self.c()),
+            GetVarArgs => multi(a, self.b()),
+            ForNLoop => vec![reg(a as u16 + 2)],
+            ForGLoop => vec![regs(
+                a as u16 + 2,
+                1 + (self.aux.unwrap_or_default() & 0xff) as u16,
+            )],
+            _ => vec![],
+        }
+    }
+
+    fn jump_targets(&self, pc: usize) -> Vec<usize> {
+        self.jump_target(pc).into_iter().collect()
+    }
+
+    fn falls_through(&self) -> bool {
+        use luau::OpCode::*;
+        !matches!(
+            self.op,
+            Jump | JumpBack | JumpX | Return | ForGPrep | ForGPrepINext | ForGPrepNext
+        ) && !(self.op == LoadB && self.c() != 0)
+    }
+
+    fn constants(&self) -> Vec<ConstantRef> {
+        use luau::OpCode::*;
+
+        let aux = self.aux.unwrap_or_default();
+        let k = |k: u32| vec![ConstantRef::Constant(k)];
+        match self.op {
+            LoadK | GetImport | DupTable | DupClosure => k(self.d() as u16 as _),
+            NewClosure => vec![ConstantRef::Proto(self.d() as u16 as _)],
+            GetGlobal | SetGlobal | GetTableKS | SetTableKS | NameCall | LoadKX | FastCall2K
+            | DepJumpIfEqK | DepJumpIfNotEqK => k(aux),
+            JumpXEqKN | JumpXEqKS => k(aux & 0xffffff),
+            AddK | SubK | MulK | DivK | ModK | PowK | AndK | OrK | IDivK => k(self.c() as _),
+            SubRK | DivRK => k(self.b() as _),
+            _ => vec![],
+        }
+    }
+}
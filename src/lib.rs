@@ -1,7 +1,7 @@
-#![feature(ptr_sub_ptr, lazy_cell, box_patterns)]
+#![feature(lazy_cell, box_patterns)]
 
 use bincode::{Decode, Encode};
-use std::{borrow::Cow, rc::Rc};
+use std::{borrow::Cow, cell::RefCell, rc::Rc};
 
 #[allow(unused_imports)]
 use nom::{
@@ -17,17 +17,17 @@ use nom::{
     sequence::{delimited, tuple},
 };
 use nom::{
-    bytes::complete::take,
     combinator::success,
-    error::{context, ErrorKind, ParseError},
-    multi::{length_count, length_data},
+    error::{context, ErrorKind, FromExternalError, ParseError as NomParseError},
     number::{complete, Endianness},
-    Parser,
+    Parser, ToUsize,
 };
 use nom_supreme::{error::*, ParserExt};
 use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
 
+pub mod instruction;
+pub mod listing;
 pub mod lua51;
 pub mod lua52;
 pub mod lua53;
@@ -50,6 +50,8 @@ pub struct LuaHeader {
     pub number_integral: bool,
     // for luajit
     pub lj_flags: u8,
+    // for luau, the bytecode version is `format_version`
+    pub types_version: u8,
 }
 
 impl LuaHeader {
@@ -86,14 +88,27 @@ impl std::fmt::Display for LuaNumber {
     }
 }
 
-/// Constant table for luajit, notice that the index of the array part starts at 0
-#[derive(Clone, Debug, Default, Serialize, Deserialize, Encode, Decode)]
+/// Constant table for luajit and luau, notice that the index of the array part starts at 0
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, Encode, Decode)]
 pub struct ConstTable {
     pub array: Vec<LuaConstant>,
     pub hash: Vec<(LuaConstant, LuaConstant)>,
 }
 
-#[derive(Clone, Default, Deserialize, Encode, Decode)]
+impl ConstTable {
+    /// Key/value pairs of the table as in lua, the array part is indexed from 0 and its nil
+    /// slots, including `t[0]` which is usually unused, are not pairs
+    pub fn pairs(&self) -> impl Iterator<Item = (LuaConstant, &LuaConstant)> {
+        self.array
+            .iter()
+            .enumerate()
+            .filter(|&(_, v)| v != &LuaConstant::Null)
+            .map(|(i, v)| (LuaConstant::Number(LuaNumber::Integer(i as _)), v))
+            .chain(self.hash.iter().map(|(k, v)| (k.clone(), v)))
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Deserialize, Encode, Decode)]
 #[serde(untagged)]
 pub enum LuaConstant {
     #[default]
@@ -104,8 +119,21 @@ pub enum LuaConstant {
     // for luajit
     Proto(usize),
     Table(Box<ConstTable>),
-    // // for luau
-    // Imp(u32),
+    // for luau
+    Vector([f32; 4]),
+    // for luau, `path` is resolved from the constant indices packed in `id`
+    Import {
+        id: u32,
+        #[bincode(with_serde)]
+        path: Vec<Rc<ByteBuf>>,
+    },
+    // for luajit, the 64-bit cdata like `1LL`, `1ULL` and `1i`
+    Int64(i64),
+    UInt64(u64),
+    Complex {
+        re: f64,
+        im: f64,
+    },
 }
 
 impl Serialize for LuaConstant {
@@ -125,7 +153,12 @@ impl Serialize for LuaConstant {
                 }
             }
             LuaConstant::Proto(p) => p.serialize(ser),
+            LuaConstant::Int64(i) => i.serialize(ser),
+            LuaConstant::UInt64(i) => i.serialize(ser),
+            LuaConstant::Complex { re, im } => (re, im).serialize(ser),
             LuaConstant::Table(t) => t.serialize(ser),
+            LuaConstant::Vector(v) => v.serialize(ser),
+            LuaConstant::Import { .. } => ser.serialize_str(&self.to_literal()),
         }
     }
 }
@@ -150,12 +183,30 @@ impl std::fmt::Debug for LuaConstant {
                 .field(&String::from_utf8_lossy(arg0))
                 .finish(),
             Self::Proto(i) => f.debug_tuple("Proto").field(i).finish(),
+            Self::Int64(i) => f.debug_tuple("Int64").field(i).finish(),
+            Self::UInt64(i) => f.debug_tuple("UInt64").field(i).finish(),
+            Self::Complex { re, im } => f
+                .debug_struct("Complex")
+                .field("re", re)
+                .field("im", im)
+                .finish(),
             Self::Table(box ConstTable { array, hash }) => f
                 .debug_struct("Table")
                 .field("array", array)
                 .field("hash", hash)
                 .finish(),
-            // Self::Imp(imp) => f.debug_tuple("Imp").field(imp).finish(),
+            Self::Vector(v) => f.debug_tuple("Vector").field(v).finish(),
+            Self::Import { id, path } => f
+                .debug_struct("Import")
+                .field("id", id)
+                .field(
+                    "path",
+                    &path
+                        .iter()
+                        .map(|s| String::from_utf8_lossy(s))
+                        .collect::<Vec<_>>(),
+                )
+                .finish(),
         }
     }
 }
@@ -199,15 +250,20 @@ pub struct LuaChunk {
     pub num_params: u8,
     /// Equivalent to framesize for luajit
     pub max_stack: u8,
-    /// for luajit
+    /// for luajit and luau, see [`Self::flags`] and [`Self::luau_flags`]
     pub flags: u8,
+    /// for luau
+    pub type_info: Option<luau::TypeInfo>,
     pub is_vararg: Option<LuaVarArgInfo>,
     pub instructions: Vec<u32>,
     pub constants: Vec<LuaConstant>,
     /// for luajit
     pub num_constants: Vec<LuaNumber>,
     pub prototypes: Vec<Self>,
+    /// `(line, 0)` of each instruction for lua51~lua53, luajit and luau, `(pc, line)` of the absolute line info for lua54
     pub source_lines: Vec<(u32, u32)>,
+    /// for lua54, line delta of each instruction, -128 marks an absolute line in `source_lines`
+    pub line_info: Vec<i8>,
     pub locals: Vec<LuaLocal>,
     /// for lua53
     pub upvalue_infos: Vec<UpVal>,
@@ -237,7 +293,11 @@ impl LuaChunk {
     }
 
     pub fn flags(&self) -> luajit::ProtoFlags {
-        luajit::ProtoFlags::from_bits(self.flags).unwrap()
+        luajit::ProtoFlags::from_bits_retain(self.flags)
+    }
+
+    pub fn luau_flags(&self) -> luau::ProtoFlags {
+        luau::ProtoFlags::from_bits_retain(self.flags)
     }
 
     pub fn is_empty(&self) -> bool {
@@ -245,6 +305,497 @@ impl LuaChunk {
     }
 }
 
+/// Error of decoding the raw instructions of a chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    InvalidOpcode {
+        pc: usize,
+        raw: u32,
+    },
+    /// The instruction at `pc` expects more words than the chunk has
+    Truncated {
+        pc: usize,
+    },
+    /// The header version has no instruction decoder
+    UnsupportedVersion {
+        version: u8,
+    },
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidOpcode { pc, raw } => write!(f, "invalid opcode at pc {pc}: {raw:#010x}"),
+            Self::Truncated { pc } => write!(f, "truncated instruction at pc {pc}"),
+            Self::UnsupportedVersion { version } => {
+                write!(f, "unsupported version {version:#04x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Error of serializing a [`LuaBytecode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpError {
+    UnsupportedVersion {
+        version: u8,
+    },
+    /// The header declares a size which has no encoding
+    UnsupportedSize {
+        size: u8,
+    },
+    /// A count or a value does not fit into its field
+    Overflow {
+        value: u64,
+    },
+    /// A constant which can not be represented in the target format
+    UnsupportedConstant,
+    /// A number which can not be represented exactly in the number format of the header
+    Inexact,
+}
+
+impl std::fmt::Display for DumpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedVersion { version } => write!(f, "unsupported version {version:#04x}"),
+            Self::UnsupportedSize { size } => write!(f, "unsupported size {size}"),
+            Self::Overflow { value } => write!(f, "value {value} overflows its field"),
+            Self::UnsupportedConstant => write!(f, "unsupported constant"),
+            Self::Inexact => write!(f, "number can not be represented exactly"),
+        }
+    }
+}
+
+impl std::error::Error for DumpError {}
+
+/// Error of [`parse`], `offset` is the position in the input, `context` is the stack of the
+/// sections being parsed from the outermost, and `path` is the index of the prototype being
+/// parsed in each level, which is the index in the dump order for luajit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input ends in the middle of a structure
+    Truncated {
+        offset: usize,
+        context: Vec<&'static str>,
+        path: Vec<usize>,
+    },
+    /// The signature is known but the version is not supported
+    UnsupportedVersion { offset: usize, version: u8 },
+    /// Output of the luau compiler on failure, which is the version 0 followed by the message
+    LuauCompileError { message: String },
+    /// Unknown type tag of a constant
+    InvalidConstant {
+        offset: usize,
+        tag: u8,
+        context: Vec<&'static str>,
+        path: Vec<usize>,
+    },
+    /// A limit of [`ParseOptions`] is exceeded, `value` is the requested amount
+    LimitExceeded {
+        offset: usize,
+        limit: Limit,
+        value: usize,
+        context: Vec<&'static str>,
+        path: Vec<usize>,
+    },
+    /// Any other mismatch, `expected` describes the parser which failed and `actual` is the
+    /// byte at `offset`
+    Unexpected {
+        offset: usize,
+        expected: String,
+        actual: Option<u8>,
+        context: Vec<&'static str>,
+        path: Vec<usize>,
+    },
+}
+
+impl ParseError {
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::Truncated { offset, .. }
+            | Self::UnsupportedVersion { offset, .. }
+            | Self::InvalidConstant { offset, .. }
+            | Self::LimitExceeded { offset, .. }
+            | Self::Unexpected { offset, .. } => *offset,
+            Self::LuauCompileError { .. } => 0,
+        }
+    }
+
+    pub fn context(&self) -> &[&'static str] {
+        match self {
+            Self::Truncated { context, .. }
+            | Self::InvalidConstant { context, .. }
+            | Self::LimitExceeded { context, .. }
+            | Self::Unexpected { context, .. } => context,
+            Self::UnsupportedVersion { .. } | Self::LuauCompileError { .. } => &[],
+        }
+    }
+
+    pub fn path(&self) -> &[usize] {
+        match self {
+            Self::Truncated { path, .. }
+            | Self::InvalidConstant { path, .. }
+            | Self::LimitExceeded { path, .. }
+            | Self::Unexpected { path, .. } => path,
+            Self::UnsupportedVersion { .. } | Self::LuauCompileError { .. } => &[],
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated { offset, .. } => write!(f, "truncated input at {offset:#x}")?,
+            Self::UnsupportedVersion { offset, version } => {
+                write!(f, "unsupported version {version:#04x} at {offset:#x}")?
+            }
+            Self::LuauCompileError { message } => write!(f, "luau compile error: {message}")?,
+            Self::InvalidConstant { offset, tag, .. } => {
+                write!(f, "invalid constant tag {tag} at {offset:#x}")?
+            }
+            Self::LimitExceeded {
+                offset,
+                limit,
+                value,
+                ..
+            } => write!(f, "{limit:?} limit exceeded by {value} at {offset:#x}")?,
+            Self::Unexpected {
+                offset,
+                expected,
+                actual,
+                ..
+            } => {
+                write!(f, "expected {expected} at {offset:#x}")?;
+                if let Some(b) = actual {
+                    write!(f, ", found {b:#04x}")?;
+                }
+            }
+        }
+        if !self.context().is_empty() {
+            write!(f, " in {}", self.context().join(" > "))?;
+        }
+        if !self.path().is_empty() {
+            write!(f, ", prototype path {:?}", self.path())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Kind of the limits in [`ParseOptions`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Allocation,
+    Depth,
+    StringLength,
+    Count,
+}
+
+/// Resource limits for parsing untrusted bytecode, which are enforced by the parsers running
+/// inside of [`with_options`], e.g. by [`parse_with`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Approximate total bytes allocated for the elements and strings
+    pub max_alloc: usize,
+    /// Max nesting level of the prototypes, the main chunk is at level 1
+    pub max_depth: usize,
+    pub max_string_len: usize,
+    /// Max element count of each vector, e.g. instructions, constants or locals
+    pub max_count: usize,
+}
+
+impl Default for ParseOptions {
+    /// No limits but the depth, which is `LUAI_MAXCCALLS` of lua
+    fn default() -> Self {
+        Self {
+            max_alloc: usize::MAX,
+            max_depth: 200,
+            max_string_len: usize::MAX,
+            max_count: usize::MAX,
+        }
+    }
+}
+
+struct Limits {
+    options: ParseOptions,
+    alloc: usize,
+    /// Level of the prototype being parsed by the recursive parsers
+    depth: usize,
+}
+
+thread_local! {
+    static LIMITS: RefCell<Option<Limits>> = const { RefCell::new(None) };
+}
+
+/// Run the parsers in `f` with the limits of `options`, the chunk parsers apply the default
+/// options outside of it
+pub fn with_options<R>(options: &ParseOptions, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Limits>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            LIMITS.set(self.0.take());
+        }
+    }
+
+    let _restore = Restore(LIMITS.replace(Some(Limits {
+        options: options.clone(),
+        alloc: 0,
+        depth: 1,
+    })));
+    f()
+}
+
+/// Run the chunk parser with the default options unless some are installed, so the parsers
+/// called directly are limited as well
+pub(crate) fn limited<'a, O>(
+    mut parser: impl Parser<&'a [u8], O, ErrorTree<&'a [u8]>>,
+) -> impl Parser<&'a [u8], O, ErrorTree<&'a [u8]>> {
+    move |input| {
+        if LIMITS.with_borrow(Option::is_some) {
+            parser.parse(input)
+        } else {
+            with_options(&ParseOptions::default(), || parser.parse(input))
+        }
+    }
+}
+
+fn check_limits<I>(
+    input: I,
+    check: impl FnOnce(&mut Limits) -> Option<(Limit, usize)>,
+) -> IResult<I, ()> {
+    match LIMITS.with_borrow_mut(|l| l.as_mut().and_then(check)) {
+        Some((limit, value)) => fail_with(
+            input,
+            ParseError::LimitExceeded {
+                offset: 0,
+                limit,
+                value,
+                context: vec![],
+                path: vec![],
+            },
+        ),
+        None => Ok((input, ())),
+    }
+}
+
+/// Account `n` elements of `size` bytes
+pub(crate) fn check_count<I>(input: I, n: usize, size: usize) -> IResult<I, ()> {
+    check_limits(input, |l| {
+        if n > l.options.max_count {
+            return Some((Limit::Count, n));
+        }
+        l.alloc = l.alloc.saturating_add(n.saturating_mul(size));
+        (l.alloc > l.options.max_alloc).then_some((Limit::Allocation, l.alloc))
+    })
+}
+
+pub(crate) fn check_string<I>(input: I, n: usize) -> IResult<I, ()> {
+    check_limits(input, |l| {
+        if n > l.options.max_string_len {
+            return Some((Limit::StringLength, n));
+        }
+        l.alloc = l.alloc.saturating_add(n);
+        (l.alloc > l.options.max_alloc).then_some((Limit::Allocation, l.alloc))
+    })
+}
+
+/// Check the nesting level of a prototype built from the already parsed ones, for the parsers
+/// of the flat layouts
+pub(crate) fn check_depth<I>(input: I, chunk: &LuaChunk) -> IResult<I, ()> {
+    fn depth(chunk: &LuaChunk) -> usize {
+        1 + chunk.prototypes.iter().map(depth).max().unwrap_or(0)
+    }
+
+    // the children are checked before, so the recursion is bounded
+    check_limits(input, |l| {
+        let depth = depth(chunk);
+        (depth > l.options.max_depth).then_some((Limit::Depth, depth))
+    })
+}
+
+/// Run the parser of a child prototype one level deeper
+fn nested<I: Clone, O>(input: I, parser: impl FnOnce(I) -> IResult<I, O>) -> IResult<I, O> {
+    let result = check_limits(input.clone(), |l| {
+        l.depth += 1;
+        (l.depth > l.options.max_depth).then_some((Limit::Depth, l.depth))
+    })
+    .and_then(|_| parser(input));
+    LIMITS.with_borrow_mut(|l| {
+        if let Some(l) = l {
+            l.depth -= 1;
+        }
+    });
+    result
+}
+
+/// Like [`nom::multi::length_count`], but checks the count against the limits
+pub(crate) fn length_count<'a, O, N, C, P>(
+    mut count: C,
+    mut parser: P,
+) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Vec<O>>
+where
+    N: ToUsize,
+    C: Parser<&'a [u8], N, ErrorTree<&'a [u8]>>,
+    P: Parser<&'a [u8], O, ErrorTree<&'a [u8]>>,
+{
+    move |input| {
+        let (input, n) = count.parse(input)?;
+        count_with(&mut parser, n.to_usize(), input)
+    }
+}
+
+/// Like [`nom::multi::count`], but checks the count against the limits
+pub(crate) fn count<'a, O, P>(
+    mut parser: P,
+    n: usize,
+) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Vec<O>>
+where
+    P: Parser<&'a [u8], O, ErrorTree<&'a [u8]>>,
+{
+    move |input| count_with(&mut parser, n, input)
+}
+
+fn count_with<'a, O, P>(parser: &mut P, n: usize, mut input: &'a [u8]) -> IResult<&'a [u8], Vec<O>>
+where
+    P: Parser<&'a [u8], O, ErrorTree<&'a [u8]>>,
+{
+    check_count(input, n, core::mem::size_of::<O>())?;
+    let mut result = vec![];
+    for _ in 0..n {
+        let (rest, o) = parser.parse(input)?;
+        result.push(o);
+        input = rest;
+    }
+    Ok((input, result))
+}
+
+/// Like [`nom::bytes::complete::take`], but checks the length against the limits
+pub(crate) fn take<'a, N: ToUsize>(n: N) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], &'a [u8]> {
+    let n = n.to_usize();
+    move |input| {
+        check_string(input, n)?;
+        nom::bytes::complete::take(n)(input)
+    }
+}
+
+/// Like [`nom::multi::length_data`], but checks the length against the limits
+pub(crate) fn length_data<'a, N, C>(
+    mut count: C,
+) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], &'a [u8]>
+where
+    N: ToUsize,
+    C: Parser<&'a [u8], N, ErrorTree<&'a [u8]>>,
+{
+    move |input| {
+        let (input, n) = count.parse(input)?;
+        take(n)(input)
+    }
+}
+
+/// Output buffer of the dump functions, which encodes the primitives as declared by the header
+pub struct DumpState<'h> {
+    pub header: &'h LuaHeader,
+    pub out: Vec<u8>,
+}
+
+impl<'h> DumpState<'h> {
+    pub fn new(header: &'h LuaHeader) -> Self {
+        Self {
+            header,
+            out: vec![],
+        }
+    }
+
+    pub fn byte(&mut self, b: u8) {
+        self.out.push(b);
+    }
+
+    pub fn bytes(&mut self, b: &[u8]) {
+        self.out.extend_from_slice(b);
+    }
+
+    /// Write the low `size` bytes of `v` in the endianness of the header
+    pub fn uint(&mut self, v: u64, size: u8) -> Result<(), DumpError> {
+        if !matches!(size, 1 | 2 | 4 | 8) {
+            return Err(DumpError::UnsupportedSize { size });
+        }
+        if size < 8 && v >> (size * 8) != 0 {
+            return Err(DumpError::Overflow { value: v });
+        }
+        let bytes = &v.to_le_bytes()[..size as usize];
+        if self.header.big_endian {
+            self.out.extend(bytes.iter().rev());
+        } else {
+            self.out.extend_from_slice(bytes);
+        }
+        Ok(())
+    }
+
+    /// Inverse of `lua_int`
+    pub fn int(&mut self, v: u64) -> Result<(), DumpError> {
+        self.uint(v, self.header.int_size)
+    }
+
+    /// Length prefix of a vector, as an int
+    pub fn count(&mut self, n: usize) -> Result<(), DumpError> {
+        self.int(n as u64)
+    }
+
+    /// Inverse of `lua_size_t`
+    pub fn size_t(&mut self, v: u64) -> Result<(), DumpError> {
+        self.uint(v, self.header.size_t_size)
+    }
+
+    pub fn instruction(&mut self, i: u32) -> Result<(), DumpError> {
+        match self.header.instruction_size {
+            4 => self.uint(i as u64, 4),
+            size => Err(DumpError::UnsupportedSize { size }),
+        }
+    }
+
+    /// Inverse of `lua_number`, integers are converted into floats if the header requires,
+    /// as long as the conversion is exact
+    pub fn number(&mut self, n: &LuaNumber) -> Result<(), DumpError> {
+        let size = self.header.number_size;
+        if self.header.number_integral {
+            let i = match *n {
+                LuaNumber::Integer(i) => i,
+                // the cast saturates, so 2^63 would round trip through i64::MAX
+                LuaNumber::Float(f)
+                    if (-(2f64.powi(63))..2f64.powi(63)).contains(&f) && f as i64 as f64 == f =>
+                {
+                    f as i64
+                }
+                LuaNumber::Float(_) => return Err(DumpError::Inexact),
+            };
+            if size < 8 && (i >> (size * 8 - 1)) != 0 && (i >> (size * 8 - 1)) != -1 {
+                return Err(DumpError::Overflow { value: i as u64 });
+            }
+            let mask = if size < 8 {
+                (1u64 << (size * 8)) - 1
+            } else {
+                !0
+            };
+            self.uint(i as u64 & mask, size)
+        } else {
+            let f = match *n {
+                LuaNumber::Integer(i) if i as f64 as i64 == i && i != i64::MAX => i as f64,
+                LuaNumber::Integer(_) => return Err(DumpError::Inexact),
+                LuaNumber::Float(f) => f,
+            };
+            match size {
+                8 => self.uint(f.to_bits(), 8),
+                4 if f as f32 as f64 != f && !f.is_nan() => Err(DumpError::Inexact),
+                4 => self.uint((f as f32).to_bits() as u64, 4),
+                size => Err(DumpError::UnsupportedSize { size }),
+            }
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Encode, Decode)]
 pub struct LuaBytecode {
     pub header: LuaHeader,
@@ -398,7 +949,7 @@ fn lua_header(input: &[u8]) -> IResult<&[u8], LuaHeader, ErrorTree<&[u8]>> {
     Ok((rest, result))
 }
 
-fn must<I, O, E: ParseError<I>, P: Parser<I, O, E>>(
+fn must<I, O, E: NomParseError<I>, P: Parser<I, O, E>>(
     cond: bool,
     mut parser: P,
 ) -> impl FnMut(I) -> IResult<I, O, E> {
@@ -485,7 +1036,14 @@ fn lua_number<'a>(header: &LuaHeader) -> impl Parser<&'a [u8], LuaNumber, ErrorT
 }
 
 pub fn lua_bytecode(input: &[u8]) -> IResult<&[u8], LuaBytecode, ErrorTree<&[u8]>> {
-    let (input, header) = alt((lua_header, luajit::lj_header))(input)?;
+    // luau is the last resort, as it is detected by the version bytes only
+    let (input, header) = alt((
+        lua_header,
+        luajit::lj_header,
+        unsupported_version,
+        luau::compile_error,
+        luau::luau_header,
+    ))(input)?;
     log::trace!("header: {header:?}");
     let (input, main_chunk) = match header.version() {
         LUA51 => lua51::lua_chunk(&header).parse(input)?,
@@ -493,18 +1051,323 @@ pub fn lua_bytecode(input: &[u8]) -> IResult<&[u8], LuaBytecode, ErrorTree<&[u8]
         LUA53 => lua53::lua_chunk(&header).parse(input)?,
         LUA54 => lua54::lua_chunk(&header).parse(input)?,
         LUAJ1 | LUAJ2 => luajit::lj_chunk(&header).parse(input)?,
-        _ => context("unsupported lua version", fail)(input)?,
+        LUAU => luau::lua_chunk(&header).parse(input)?,
+        version => fail_with(
+            input,
+            ParseError::UnsupportedVersion {
+                offset: 0,
+                version: version.0,
+            },
+        )?,
     };
     Ok((input, LuaBytecode { header, main_chunk }))
 }
 
-pub fn parse(input: &[u8]) -> Result<LuaBytecode, String> {
-    lua_bytecode(input).map(|x| x.1).map_err(|e| {
-        format!(
-            "{:#?}",
-            e.map(|e| e.map_locations(|p| unsafe { p.as_ptr().sub_ptr(input.as_ptr()) }))
+/// Fallback of the header parsers, which reports the version byte after a known signature
+fn unsupported_version(input: &[u8]) -> IResult<&[u8], LuaHeader> {
+    let (input, _) = alt((tag(b"\x1bLua"), tag(b"\x1bLJ")))(input)?;
+    let (_, version) = be_u8(input)?;
+    fail_with(input, ParseError::UnsupportedVersion { offset: 0, version })
+}
+
+/// Fail with a structured error, its location is filled in by [`parse`]
+pub(crate) fn fail_with<I, O>(input: I, e: ParseError) -> IResult<I, O> {
+    Err(nom::Err::Error(ErrorTree::from_external_error(
+        input,
+        ErrorKind::Verify,
+        e,
+    )))
+}
+
+/// Fail with [`ParseError::Unexpected`]
+pub(crate) fn fail_expected<I, O>(input: I, expected: &str) -> IResult<I, O> {
+    fail_with(
+        input,
+        ParseError::Unexpected {
+            offset: 0,
+            expected: expected.into(),
+            actual: None,
+            context: vec![],
+            path: vec![],
+        },
+    )
+}
+
+/// Error of a child prototype, the locations are the lengths of the remaining input, which are
+/// independent of the slice being parsed
+#[derive(Debug)]
+struct ProtoError {
+    index: usize,
+    error: ErrorTree<usize>,
+}
+
+impl std::fmt::Display for ProtoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "prototype {}: {}", self.index, self.error)
+    }
+}
+
+impl std::error::Error for ProtoError {}
+
+/// Tag the error of parsing a prototype with its index
+pub(crate) fn in_proto<'a>(
+    index: usize,
+    input: &'a [u8],
+) -> impl FnOnce(ErrorTree<&'a [u8]>) -> ErrorTree<&'a [u8]> {
+    move |e| {
+        ErrorTree::from_external_error(
+            input,
+            ErrorKind::Count,
+            ProtoError {
+                index,
+                error: e.map_locations(|l| l.len()),
+            },
         )
-    })
+    }
+}
+
+/// `length_count` for the child prototypes, which records the index of the failing one
+pub(crate) fn length_protos<'a, C, P>(
+    mut count: C,
+    mut proto: P,
+) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Vec<LuaChunk>>
+where
+    C: Parser<&'a [u8], usize, ErrorTree<&'a [u8]>>,
+    P: Parser<&'a [u8], LuaChunk, ErrorTree<&'a [u8]>>,
+{
+    move |input| {
+        let (mut input, n) = count.parse(input)?;
+        check_count(input, n, core::mem::size_of::<LuaChunk>())?;
+        let mut result = vec![];
+        for index in 0..n {
+            let (rest, p) =
+                nested(input, |i| proto.parse(i)).map_err(|e| e.map(in_proto(index, input)))?;
+            result.push(p);
+            input = rest;
+        }
+        Ok((input, result))
+    }
+}
+
+/// Location of the most relevant error of a tree, the deepest one with the highest priority
+fn error_rank(e: &ErrorTree<usize>) -> (std::cmp::Reverse<usize>, u8) {
+    match e {
+        ErrorTree::Base { location, kind } => (
+            std::cmp::Reverse(*location),
+            match kind {
+                BaseErrorKind::External(_) => 3,
+                BaseErrorKind::Expected(Expectation::Something | Expectation::Eof) => 2,
+                // the disabled branches of `must`
+                BaseErrorKind::Kind(ErrorKind::Switch) => 0,
+                _ => 1,
+            },
+        ),
+        ErrorTree::Stack { base, .. } => error_rank(base),
+        ErrorTree::Alt(alts) => alts.iter().map(error_rank).max().unwrap_or_default(),
+    }
+}
+
+impl ParseError {
+    fn from_tree(
+        e: ErrorTree<usize>,
+        input: &[u8],
+        mut context: Vec<&'static str>,
+        mut path: Vec<usize>,
+    ) -> Self {
+        match e {
+            ErrorTree::Stack { base, contexts } => {
+                context.extend(contexts.into_iter().rev().filter_map(|(_, c)| match c {
+                    StackContext::Context(c) => Some(c),
+                    StackContext::Kind(_) => None,
+                }));
+                Self::from_tree(*base, input, context, path)
+            }
+            ErrorTree::Alt(alts) => match alts.into_iter().max_by_key(error_rank) {
+                Some(e) => Self::from_tree(e, input, context, path),
+                None => Self::Unexpected {
+                    offset: 0,
+                    expected: "input".into(),
+                    actual: input.first().copied(),
+                    context,
+                    path,
+                },
+            },
+            ErrorTree::Base { location, kind } => {
+                let offset = input.len().saturating_sub(location);
+                let actual = input.get(offset).copied();
+                let expected = match kind {
+                    BaseErrorKind::Expected(Expectation::Something | Expectation::Eof) => {
+                        return Self::Truncated {
+                            offset,
+                            context,
+                            path,
+                        }
+                    }
+                    BaseErrorKind::External(e) => {
+                        let e = match e.downcast::<ProtoError>() {
+                            Ok(p) => {
+                                path.push(p.index);
+                                return Self::from_tree(p.error, input, context, path);
+                            }
+                            Err(e) => e,
+                        };
+                        match e.downcast::<ParseError>() {
+                            Ok(e) => {
+                                return match *e {
+                                    Self::Truncated { .. } => Self::Truncated {
+                                        offset,
+                                        context,
+                                        path,
+                                    },
+                                    Self::UnsupportedVersion { version, .. } => {
+                                        Self::UnsupportedVersion { offset, version }
+                                    }
+                                    e @ Self::LuauCompileError { .. } => e,
+                                    Self::InvalidConstant { tag, .. } => Self::InvalidConstant {
+                                        offset,
+                                        tag,
+                                        context,
+                                        path,
+                                    },
+                                    Self::LimitExceeded { limit, value, .. } => {
+                                        Self::LimitExceeded {
+                                            offset,
+                                            limit,
+                                            value,
+                                            context,
+                                            path,
+                                        }
+                                    }
+                                    Self::Unexpected { expected, .. } => Self::Unexpected {
+                                        offset,
+                                        expected,
+                                        actual,
+                                        context,
+                                        path,
+                                    },
+                                }
+                            }
+                            Err(e) => e.to_string(),
+                        }
+                    }
+                    BaseErrorKind::Expected(e) => e.to_string(),
+                    BaseErrorKind::Kind(k) => k.description().to_lowercase(),
+                };
+                Self::Unexpected {
+                    offset,
+                    expected,
+                    actual,
+                    context,
+                    path,
+                }
+            }
+        }
+    }
+}
+
+pub fn parse(input: &[u8]) -> Result<LuaBytecode, ParseError> {
+    parse_with(input, &ParseOptions::default())
+}
+
+/// Parse with the resource limits of `options`
+pub fn parse_with(input: &[u8], options: &ParseOptions) -> Result<LuaBytecode, ParseError> {
+    with_options(options, || lua_bytecode(input))
+        .map(|x| x.1)
+        .map_err(|e| match e {
+            nom::Err::Error(e) | nom::Err::Failure(e) => {
+                ParseError::from_tree(e.map_locations(|l| l.len()), input, vec![], vec![])
+            }
+            nom::Err::Incomplete(_) => ParseError::Truncated {
+                offset: input.len(),
+                context: vec![],
+                path: vec![],
+            },
+        })
+}
+
+/// `LUAC_DATA` of lua52 ~ lua54, which catches conversion errors of the files
+pub const LUAC_DATA: &[u8] = b"\x19\x93\r\n\x1a\n";
+
+/// Inverse of `lua_header`
+fn dump_lua_header(d: &mut DumpState) -> Result<(), DumpError> {
+    let h = d.header;
+    d.bytes(b"\x1BLua");
+    d.byte(h.lua_version);
+    d.byte(h.format_version);
+    match h.version() {
+        LUA51 | LUA52 => {
+            d.bytes(&[
+                !h.big_endian as u8,
+                h.int_size,
+                h.size_t_size,
+                h.instruction_size,
+                h.number_size,
+                h.number_integral as u8,
+            ]);
+            if h.version() == LUA52 {
+                d.bytes(LUAC_DATA);
+            }
+        }
+        _ => {
+            d.bytes(LUAC_DATA);
+            if h.version() == LUA53 {
+                d.bytes(&[h.int_size, h.size_t_size]);
+            }
+            // lua_Integer is always 8 bytes
+            d.bytes(&[h.instruction_size, 8, h.number_size]);
+            d.uint(0x5678, 8)?;
+            d.number(&LuaNumber::Float(370.5))?;
+        }
+    }
+    Ok(())
+}
+
+impl LuaBytecode {
+    /// Serialize the bytecode into the binary format of its header version
+    pub fn dump(&self) -> Result<Vec<u8>, DumpError> {
+        retarget(self, &self.header)
+    }
+}
+
+/// Serialize the bytecode with another header of the same version, which re-encodes the sizes,
+/// the endianness and the number representation, e.g. to normalize the bytecode of uncommon
+/// builds into the layout of the host.
+///
+/// Fails with [`DumpError::Overflow`] or [`DumpError::Inexact`] if a value does not fit into the
+/// new layout
+pub fn retarget(bytecode: &LuaBytecode, header: &LuaHeader) -> Result<Vec<u8>, DumpError> {
+    if header.lua_version != bytecode.header.lua_version {
+        return Err(DumpError::UnsupportedVersion {
+            version: header.lua_version,
+        });
+    }
+
+    let mut d = DumpState::new(header);
+    let chunk = &bytecode.main_chunk;
+    match header.version() {
+        LUA51 => {
+            dump_lua_header(&mut d)?;
+            lua51::dump_chunk(&mut d, chunk)?;
+        }
+        LUA52 => {
+            dump_lua_header(&mut d)?;
+            lua52::dump_chunk(&mut d, chunk)?;
+        }
+        LUA53 => {
+            dump_lua_header(&mut d)?;
+            d.byte(chunk.upvalue_infos.len() as _);
+            lua53::dump_chunk(&mut d, chunk)?;
+        }
+        LUA54 => {
+            dump_lua_header(&mut d)?;
+            d.byte(chunk.upvalue_infos.len() as _);
+            lua54::dump_chunk(&mut d, chunk)?;
+        }
+        LUAJ1 | LUAJ2 => luajit::dump_lj_chunk(&mut d, chunk)?,
+        LUAU => luau::dump_chunk(&mut d, chunk)?,
+        v => return Err(DumpError::UnsupportedVersion { version: v.0 }),
+    }
+    Ok(d.out)
 }
 
 #[cfg(feature = "rmp-serde")]
@@ -526,6 +1389,7 @@ impl std::fmt::Display for LuaVersion {
         match *self {
             LUAJ1 => write!(f, "luajit1"),
             LUAJ2 => write!(f, "luajit2"),
+            LUAU => write!(f, "luau"),
             v => write!(f, "lua{:x}", v.0),
         }
     }
@@ -535,6 +1399,10 @@ impl LuaVersion {
     pub fn is_luajit(self) -> bool {
         matches!(self, LUAJ1 | LUAJ2)
     }
+
+    pub fn is_luau(self) -> bool {
+        self == LUAU
+    }
 }
 
 pub const LUA51: LuaVersion = LuaVersion(0x51);
@@ -543,3 +1411,5 @@ pub const LUA53: LuaVersion = LuaVersion(0x53);
 pub const LUA54: LuaVersion = LuaVersion(0x54);
 pub const LUAJ1: LuaVersion = LuaVersion(0x11);
 pub const LUAJ2: LuaVersion = LuaVersion(0x12);
+/// Luau has no signature nor a version in the sense of lua, this is only an identifier
+pub const LUAU: LuaVersion = LuaVersion(0x20);
@@ -0,0 +1,797 @@
+//! Human readable listings of the parsed bytecode, in the layout of the reference tools
+
+use super::*;
+use std::{collections::HashSet, fmt::Write};
+
+// writing into a String never fails
+macro_rules! out {
+    ($self:ident, $($arg:tt)*) => {
+        let _ = write!($self.out, $($arg)*);
+    };
+}
+
+/// Render PUC lua bytecode like `luac -l`, `full` adds the constants, locals and upvalues of each
+/// function like `luac -l -l`
+///
+/// The addresses of the functions are the ones of the parsed [`LuaChunk`]s
+pub fn luac(bytecode: &LuaBytecode, full: bool) -> Result<String, DecodeError> {
+    let version = bytecode.header.version();
+    if !matches!(version, LUA51 | LUA52 | LUA53 | LUA54) {
+        return Err(DecodeError::UnsupportedVersion { version: version.0 });
+    }
+
+    let mut luac = Luac {
+        out: String::new(),
+        version,
+        full,
+    };
+    luac.function(&bytecode.main_chunk, b"=?")?;
+    Ok(luac.out)
+}
+
+/// Render luajit bytecode like `luajit -bl`, the child functions are listed before their parent
+pub fn luajit(bytecode: &LuaBytecode) -> Result<String, DecodeError> {
+    let header = &bytecode.header;
+    if !header.version().is_luajit() {
+        return Err(DecodeError::UnsupportedVersion {
+            version: header.lua_version,
+        });
+    }
+
+    let name = &bytecode.main_chunk.name;
+    let mut luajit = LuaJit {
+        out: String::new(),
+        header,
+        chunk_name: if name.is_empty() { b"=?" } else { name },
+    };
+    luajit.function(&bytecode.main_chunk)?;
+    Ok(luajit.out)
+}
+
+/// `%.14g` of C, the default `LUA_NUMBER_FMT`
+fn number_g14(n: f64) -> String {
+    if n.is_nan() {
+        return if n.is_sign_negative() { "-nan" } else { "nan" }.into();
+    }
+    if n.is_infinite() {
+        return if n < 0.0 { "-inf" } else { "inf" }.into();
+    }
+    if n == 0.0 {
+        return if n.is_sign_negative() { "-0" } else { "0" }.into();
+    }
+
+    let trim = |s: &str| -> String {
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.').into()
+        } else {
+            s.into()
+        }
+    };
+    let sci = format!("{n:.13e}");
+    let Some((mantissa, exp)) = sci.split_once('e') else {
+        return sci;
+    };
+    let exp = exp.parse::<i32>().unwrap_or_default();
+    if !(-4..14).contains(&exp) {
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{}e{sign}{:02}", trim(mantissa), exp.abs())
+    } else {
+        trim(&format!("{n:.*}", (13 - exp) as usize))
+    }
+}
+
+/// Plural suffix, `SS` of luac.c
+fn ss(n: usize) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+struct Luac {
+    out: String,
+    version: LuaVersion,
+    full: bool,
+}
+
+impl Luac {
+    fn function(&mut self, f: &LuaChunk, source: &[u8]) -> Result<(), DecodeError> {
+        // nested functions inherit the source of their parent
+        let source = if f.name.is_empty() { source } else { &f.name };
+
+        self.header(f, source);
+        if self.version == LUA54 {
+            self.code54(f)?;
+        } else {
+            self.code(f)?;
+        }
+        if self.full {
+            self.debug(f);
+        }
+        for p in &f.prototypes {
+            self.function(p, source)?;
+        }
+        Ok(())
+    }
+
+    fn header(&mut self, f: &LuaChunk, source: &[u8]) {
+        let s = match source.first() {
+            Some(b'@' | b'=') => String::from_utf8_lossy(&source[1..]),
+            Some(0x1b) => "(bstring)".into(),
+            _ => "(string)".into(),
+        };
+        let n = f.instructions.len();
+        let bytes = if self.version == LUA51 {
+            format!(", {} bytes", n * 4)
+        } else {
+            String::new()
+        };
+        out!(
+            self,
+            "\n{} <{s}:{},{}> ({n} instruction{}{bytes} at {f:p})\n",
+            if f.line_defined == 0 {
+                "main"
+            } else {
+                "function"
+            },
+            f.line_defined,
+            f.last_line_defined,
+            ss(n),
+        );
+        let params = f.num_params as usize;
+        let slots = f.max_stack as usize;
+        let upvalues = f.num_upvalues as usize;
+        out!(
+            self,
+            "{params}{} param{}, {slots} slot{}, {upvalues} upvalue{}, ",
+            if f.is_vararg.is_some() { "+" } else { "" },
+            ss(params),
+            ss(slots),
+            ss(upvalues),
+        );
+        let (locals, constants, functions) =
+            (f.locals.len(), f.constants.len(), f.prototypes.len());
+        out!(
+            self,
+            "{locals} local{}, {constants} constant{}, {functions} function{}\n",
+            ss(locals),
+            ss(constants),
+            ss(functions),
+        );
+    }
+
+    fn constant(&mut self, f: &LuaChunk, i: usize) {
+        match f.constants.get(i) {
+            Some(LuaConstant::Null) => {
+                out!(self, "nil");
+            }
+            Some(LuaConstant::Bool(b)) => {
+                out!(self, "{b}");
+            }
+            Some(LuaConstant::Number(LuaNumber::Integer(n))) => {
+                out!(self, "{n}");
+            }
+            Some(LuaConstant::Number(LuaNumber::Float(n))) => {
+                let s = number_g14(*n);
+                // lua53 and later mark floats which look like integers
+                let suffix =
+                    if self.version >= LUA53 && s.chars().all(|c| c == '-' || c.is_ascii_digit()) {
+                        ".0"
+                    } else {
+                        ""
+                    };
+                out!(self, "{s}{suffix}");
+            }
+            Some(LuaConstant::String(s)) => {
+                self.out.push('"');
+                for &c in s.iter() {
+                    match c {
+                        b'"' => self.out.push_str("\\\""),
+                        b'\\' => self.out.push_str("\\\\"),
+                        0x07 => self.out.push_str("\\a"),
+                        0x08 => self.out.push_str("\\b"),
+                        0x0c => self.out.push_str("\\f"),
+                        b'\n' => self.out.push_str("\\n"),
+                        b'\r' => self.out.push_str("\\r"),
+                        b'\t' => self.out.push_str("\\t"),
+                        0x0b => self.out.push_str("\\v"),
+                        0x20..=0x7e => self.out.push(c as char),
+                        _ => {
+                            out!(self, "\\{c:03}");
+                        }
+                    }
+                }
+                self.out.push('"');
+            }
+            _ => self.out.push('?'),
+        }
+    }
+
+    /// Constant comment of the RK operands, `-` for registers
+    fn rk_comment(&mut self, f: &LuaChunk, b: lua51::RK, c: lua51::RK) {
+        use lua51::RK;
+
+        if !b.is_constant() && !c.is_constant() {
+            return;
+        }
+        self.out.push_str("\t; ");
+        for (i, rk) in [b, c].into_iter().enumerate() {
+            if i > 0 {
+                self.out.push(' ');
+            }
+            match rk {
+                RK::Constant(k) => self.constant(f, k as _),
+                RK::Register(_) => self.out.push('-'),
+            }
+        }
+    }
+
+    fn upvalue_name(f: &LuaChunk, i: usize) -> Cow<'_, str> {
+        f.upvalue_names
+            .get(i)
+            .map_or("-".into(), |name| String::from_utf8_lossy(name))
+    }
+
+    fn prototype(&mut self, f: &LuaChunk, i: usize) {
+        match f.prototypes.get(i) {
+            Some(p) => {
+                out!(self, "\t; {p:p}");
+            }
+            None => self.out.push_str("\t; (nil)"),
+        }
+    }
+
+    fn line(&mut self, pc: usize, line: Option<i64>) {
+        out!(self, "\t{}\t", pc + 1);
+        match line {
+            Some(line) if line > 0 => {
+                out!(self, "[{line}]\t");
+            }
+            _ => self.out.push_str("[-]\t"),
+        }
+    }
+
+    /// `PrintCode` of lua51 ~ lua53, which is driven by the opcode modes
+    fn code(&mut self, f: &LuaChunk) -> Result<(), DecodeError> {
+        use lua51::{get_a, get_b, get_bx, get_c, get_op, get_sbx, OpArgMask, OpMode, RK};
+
+        let code = &f.instructions;
+        let mut pc = 0;
+        while pc < code.len() {
+            let raw = code[pc];
+            let op = get_op(raw);
+            let (name, (mode, b_mode, c_mode)) = match self.version {
+                LUA51 => lua51::OpCode::from_u8(op).map(|o| (o.name(), o.modes())),
+                LUA52 => lua52::OpCode::from_u8(op).map(|o| (o.name(), o.modes())),
+                _ => lua53::OpCode::from_u8(op).map(|o| (o.name(), o.modes())),
+            }
+            .ok_or(DecodeError::InvalidOpcode { pc, raw })?;
+            let (a, b, c) = (get_a(raw), get_b(raw), get_c(raw));
+            let (bx, sbx) = (get_bx(raw), get_sbx(raw));
+            let rk = |x: u16| RK::new(x).to_isk();
+            let myk = |x: u32| -1 - x as i64;
+
+            self.line(pc, f.source_lines.get(pc).map(|l| l.0 as i64));
+            out!(self, "{name:<9}\t");
+            match mode {
+                OpMode::ABC => {
+                    out!(self, "{a}");
+                    if b_mode != OpArgMask::N {
+                        out!(self, " {}", rk(b));
+                    }
+                    if c_mode != OpArgMask::N {
+                        out!(self, " {}", rk(c));
+                    }
+                }
+                OpMode::ABx if self.version == LUA51 => {
+                    if b_mode == OpArgMask::K {
+                        out!(self, "{a} {}", myk(bx));
+                    } else {
+                        out!(self, "{a} {bx}");
+                    }
+                }
+                OpMode::ABx => {
+                    out!(self, "{a}");
+                    match b_mode {
+                        OpArgMask::K => {
+                            out!(self, " {}", myk(bx));
+                        }
+                        OpArgMask::U => {
+                            out!(self, " {bx}");
+                        }
+                        _ => {}
+                    }
+                }
+                OpMode::AsBx if self.version == LUA51 && name == "JMP" => {
+                    out!(self, "{sbx}");
+                }
+                OpMode::AsBx => {
+                    out!(self, "{a} {sbx}");
+                }
+                OpMode::Ax => {
+                    out!(self, "{}", myk(lua52::get_ax(raw)));
+                }
+            }
+
+            match name {
+                "LOADK" => {
+                    self.out.push_str("\t; ");
+                    self.constant(f, bx as _);
+                }
+                "GETUPVAL" | "SETUPVAL" => {
+                    out!(self, "\t; {}", Self::upvalue_name(f, b as _));
+                }
+                "GETGLOBAL" | "SETGLOBAL" => {
+                    let name = match f.constants.get(bx as usize) {
+                        Some(LuaConstant::String(s)) => String::from_utf8_lossy(s),
+                        _ => "?".into(),
+                    };
+                    out!(self, "\t; {name}");
+                }
+                "GETTABUP" => {
+                    out!(self, "\t; {}", Self::upvalue_name(f, b as _));
+                    if let RK::Constant(k) = RK::new(c) {
+                        self.out.push(' ');
+                        self.constant(f, k as _);
+                    }
+                }
+                "SETTABUP" => {
+                    out!(self, "\t; {}", Self::upvalue_name(f, a as _));
+                    for x in [b, c] {
+                        if let RK::Constant(k) = RK::new(x) {
+                            self.out.push(' ');
+                            self.constant(f, k as _);
+                        }
+                    }
+                }
+                "GETTABLE" | "SELF" => {
+                    if let RK::Constant(k) = RK::new(c) {
+                        self.out.push_str("\t; ");
+                        self.constant(f, k as _);
+                    }
+                }
+                "SETTABLE" | "ADD" | "SUB" | "MUL" | "DIV" | "POW" | "EQ" | "LT" | "LE" => {
+                    self.rk_comment(f, RK::new(b), RK::new(c));
+                }
+                // MOD is only annotated since lua53
+                "MOD" | "IDIV" | "BAND" | "BOR" | "BXOR" | "SHL" | "SHR"
+                    if self.version == LUA53 =>
+                {
+                    self.rk_comment(f, RK::new(b), RK::new(c));
+                }
+                "JMP" | "FORLOOP" | "FORPREP" | "TFORLOOP" if mode == OpMode::AsBx => {
+                    out!(self, "\t; to {}", sbx as i64 + pc as i64 + 2);
+                }
+                "CLOSURE" => self.prototype(f, bx as _),
+                "SETLIST" if c == 0 => {
+                    pc += 1;
+                    let extra = *code.get(pc).ok_or(DecodeError::Truncated { pc: pc - 1 })?;
+                    out!(self, "\t; {}", extra as i32);
+                }
+                "SETLIST" => {
+                    out!(self, "\t; {c}");
+                }
+                "EXTRAARG" => {
+                    self.out.push_str("\t; ");
+                    self.constant(f, lua52::get_ax(raw) as _);
+                }
+                _ => {}
+            }
+            self.out.push('\n');
+            pc += 1;
+        }
+        Ok(())
+    }
+
+    /// `PrintCode` of lua54, which annotates each opcode on its own
+    fn code54(&mut self, f: &LuaChunk) -> Result<(), DecodeError> {
+        use lua54::{OpCode::*, *};
+
+        let code = &f.instructions;
+        for (pc, &raw) in code.iter().enumerate() {
+            let op = OpCode::from_u8(get_op(raw)).ok_or(DecodeError::InvalidOpcode { pc, raw })?;
+            let (a, b, c) = (get_a(raw), get_b(raw), get_c(raw));
+            let (bx, sb, sc, sbx) = (get_bx(raw), get_sb(raw), get_sc(raw), get_sbx(raw));
+            let k = get_k(raw);
+            let isk = if k { "k" } else { "" };
+            let extra_arg = || {
+                code.get(pc + 1)
+                    .map(|&i| get_ax(i))
+                    .ok_or(DecodeError::Truncated { pc })
+            };
+            let event = |c: u8| TM_NAMES.get(c as usize).map_or("?", |s| &s[2..]);
+
+            self.line(pc, source_line(f, pc));
+            out!(self, "{:<9}\t", op.name());
+            match op {
+                Move | Unm | BNot | Not | Len | Concat => {
+                    out!(self, "{a} {b}");
+                }
+                LoadI | LoadF => {
+                    out!(self, "{a} {sbx}");
+                }
+                LoadK => {
+                    out!(self, "{a} {bx}\t; ");
+                    self.constant(f, bx as _);
+                }
+                LoadKX => {
+                    out!(self, "{a}\t; ");
+                    self.constant(f, extra_arg()? as _);
+                }
+                LoadFalse | LFalseSkip | LoadTrue | Close | Tbc | Return1 | VarArgPrep => {
+                    out!(self, "{a}");
+                }
+                LoadNil => {
+                    out!(self, "{a} {b}\t; {} out", b as u32 + 1);
+                }
+                GetUpval | SetUpval => {
+                    out!(self, "{a} {b}\t; {}", Self::upvalue_name(f, b as _));
+                }
+                GetTabUp => {
+                    out!(self, "{a} {b} {c}\t; {} ", Self::upvalue_name(f, b as _));
+                    self.constant(f, c as _);
+                }
+                GetTable | GetI => {
+                    out!(self, "{a} {b} {c}");
+                }
+                GetField => {
+                    out!(self, "{a} {b} {c}\t; ");
+                    self.constant(f, c as _);
+                }
+                SetTabUp => {
+                    out!(
+                        self,
+                        "{a} {b} {c}{isk}\t; {} ",
+                        Self::upvalue_name(f, a as _)
+                    );
+                    self.constant(f, b as _);
+                    if k {
+                        self.out.push(' ');
+                        self.constant(f, c as _);
+                    }
+                }
+                SetTable | SetI | Self_ => {
+                    out!(self, "{a} {b} {c}{isk}");
+                    if k {
+                        self.out.push_str("\t; ");
+                        self.constant(f, c as _);
+                    }
+                }
+                SetField => {
+                    out!(self, "{a} {b} {c}{isk}\t; ");
+                    self.constant(f, b as _);
+                    if k {
+                        self.out.push(' ');
+                        self.constant(f, c as _);
+                    }
+                }
+                NewTable => {
+                    let size = c as u64 + extra_arg()? as u64 * (MAXARG_C as u64 + 1);
+                    out!(self, "{a} {b} {c}\t; {size}");
+                }
+                AddI | ShrI | ShlI => {
+                    out!(self, "{a} {b} {sc}");
+                }
+                AddK | SubK | MulK | ModK | PowK | DivK | IDivK | BAndK | BOrK | BXorK => {
+                    out!(self, "{a} {b} {c}\t; ");
+                    self.constant(f, c as _);
+                }
+                Add | Sub | Mul | Mod | Pow | Div | IDiv | BAnd | BOr | BXor | Shl | Shr => {
+                    out!(self, "{a} {b} {c}");
+                }
+                MmBin => {
+                    out!(self, "{a} {b} {c}\t; {}", event(c));
+                }
+                MmBinI => {
+                    out!(self, "{a} {sb} {c} {}\t; {}", k as u8, event(c));
+                    if k {
+                        self.out.push_str(" flip");
+                    }
+                }
+                MmBinK => {
+                    out!(self, "{a} {b} {c} {}\t; {} ", k as u8, event(c));
+                    self.constant(f, b as _);
+                    if k {
+                        self.out.push_str(" flip");
+                    }
+                }
+                Jmp => {
+                    let sj = get_sj(raw);
+                    out!(self, "{sj}\t; to {}", sj as i64 + pc as i64 + 2);
+                }
+                Eq | Lt | Le => {
+                    out!(self, "{a} {b} {}", k as u8);
+                }
+                EqK => {
+                    out!(self, "{a} {b} {}\t; ", k as u8);
+                    self.constant(f, b as _);
+                }
+                EqI | LtI | LeI | GtI | GeI => {
+                    out!(self, "{a} {sb} {}", k as u8);
+                }
+                Test => {
+                    out!(self, "{a} {}", k as u8);
+                }
+                TestSet => {
+                    out!(self, "{a} {b} {}", k as u8);
+                }
+                Call => {
+                    out!(self, "{a} {b} {c}\t; ");
+                    match b {
+                        0 => self.out.push_str("all in "),
+                        b => {
+                            out!(self, "{} in ", b - 1);
+                        }
+                    }
+                    match c {
+                        0 => self.out.push_str("all out"),
+                        c => {
+                            out!(self, "{} out", c - 1);
+                        }
+                    }
+                }
+                TailCall => {
+                    out!(self, "{a} {b} {c}{isk}\t; {} in", b as i32 - 1);
+                }
+                Return => {
+                    out!(self, "{a} {b} {c}{isk}\t; ");
+                    match b {
+                        0 => self.out.push_str("all out"),
+                        b => {
+                            out!(self, "{} out", b - 1);
+                        }
+                    }
+                }
+                Return0 => {}
+                ForLoop | TForLoop => {
+                    out!(self, "{a} {bx}\t; to {}", pc as i64 - bx as i64 + 2);
+                }
+                ForPrep => {
+                    out!(self, "{a} {bx}\t; exit to {}", pc as u64 + bx as u64 + 3);
+                }
+                TForPrep => {
+                    out!(self, "{a} {bx}\t; to {}", pc as u64 + bx as u64 + 2);
+                }
+                TForCall => {
+                    out!(self, "{a} {c}");
+                }
+                SetList => {
+                    out!(self, "{a} {b} {c}");
+                    if k {
+                        let size = c as u64 + extra_arg()? as u64 * (MAXARG_C as u64 + 1);
+                        out!(self, "\t; {size}");
+                    }
+                }
+                Closure => {
+                    out!(self, "{a} {bx}");
+                    self.prototype(f, bx as _);
+                }
+                VarArg => {
+                    out!(self, "{a} {c}\t; ");
+                    match c {
+                        0 => self.out.push_str("all out"),
+                        c => {
+                            out!(self, "{} out", c - 1);
+                        }
+                    }
+                }
+                ExtraArg => {
+                    out!(self, "{}", get_ax(raw));
+                }
+            }
+            self.out.push('\n');
+        }
+        Ok(())
+    }
+
+    /// `PrintDebug` of luac.c
+    fn debug(&mut self, f: &LuaChunk) {
+        out!(self, "constants ({}) for {f:p}:\n", f.constants.len());
+        for (i, k) in f.constants.iter().enumerate() {
+            if self.version == LUA54 {
+                let t = match k {
+                    LuaConstant::Null => "N",
+                    LuaConstant::Bool(_) => "B",
+                    LuaConstant::Number(LuaNumber::Float(_)) => "F",
+                    LuaConstant::Number(LuaNumber::Integer(_)) => "I",
+                    LuaConstant::String(_) => "S",
+                    _ => "?",
+                };
+                out!(self, "\t{i}\t{t}\t");
+            } else {
+                out!(self, "\t{}\t", i + 1);
+            }
+            self.constant(f, i);
+            self.out.push('\n');
+        }
+
+        out!(self, "locals ({}) for {f:p}:\n", f.locals.len());
+        for (i, l) in f.locals.iter().enumerate() {
+            out!(
+                self,
+                "\t{i}\t{}\t{}\t{}\n",
+                l.name,
+                l.start_pc + 1,
+                l.end_pc + 1
+            );
+        }
+
+        if self.version == LUA51 {
+            out!(self, "upvalues ({}) for {f:p}:\n", f.upvalue_names.len());
+            for (i, name) in f.upvalue_names.iter().enumerate() {
+                out!(self, "\t{i}\t{}\n", String::from_utf8_lossy(name));
+            }
+        } else {
+            out!(self, "upvalues ({}) for {f:p}:\n", f.upvalue_infos.len());
+            for (i, u) in f.upvalue_infos.iter().enumerate() {
+                out!(
+                    self,
+                    "\t{i}\t{}\t{}\t{}\n",
+                    Self::upvalue_name(f, i),
+                    u.on_stack as u8,
+                    u.id
+                );
+            }
+        }
+    }
+}
+
+struct LuaJit<'a> {
+    out: String,
+    header: &'a LuaHeader,
+    chunk_name: &'a [u8],
+}
+
+impl LuaJit<'_> {
+    /// `loc` of `jit.util.funcinfo`, as `lj_debug_pushloc` of lj_debug.c
+    fn loc(&self, f: &LuaChunk) -> String {
+        let (s, line) = (self.chunk_name, f.line_defined);
+        match s.first() {
+            Some(b'@') => {
+                let s = &s[1..];
+                let s = (1..s.len())
+                    .rev()
+                    .find(|&i| s[i] == b'/' || s[i] == b'\\')
+                    .map_or(s, |i| &s[i + 1..]);
+                format!("{}:{line}", String::from_utf8_lossy(s))
+            }
+            _ if s.len() > 40 => format!("{f:p}:{line}"),
+            Some(b'=') => format!("{}:{line}", String::from_utf8_lossy(&s[1..])),
+            _ => format!("\"{}\":{line}", String::from_utf8_lossy(s)),
+        }
+    }
+
+    fn upvalue_name(f: &LuaChunk, i: usize) -> Option<Cow<'_, str>> {
+        (i < f.num_upvalues as usize).then(|| {
+            f.upvalue_names
+                .get(i)
+                .map_or("".into(), |name| String::from_utf8_lossy(name))
+        })
+    }
+
+    /// `bcdump` of jit/bc.lua
+    fn function(&mut self, f: &LuaChunk) -> Result<(), DecodeError> {
+        for k in &f.constants {
+            if let Some(p) = match k {
+                LuaConstant::Proto(i) => f.prototypes.get(*i),
+                _ => None,
+            } {
+                self.function(p)?;
+            }
+        }
+
+        out!(
+            self,
+            "-- BYTECODE -- {}-{}\n",
+            self.loc(f),
+            f.last_line_defined
+        );
+        let code = luajit::decode(self.header, f)?;
+        let targets = code
+            .iter()
+            .filter_map(|(pc, i)| i.jump_target(*pc))
+            .collect::<HashSet<_>>();
+        for (pc, i) in &code {
+            self.instruction(f, *pc, i, targets.contains(pc));
+        }
+        self.out.push('\n');
+        Ok(())
+    }
+
+    /// `bcline` of jit/bc.lua, the listed pc starts at 1 because the function header is not dumped
+    fn instruction(&mut self, f: &LuaChunk, pc: usize, i: &luajit::Instruction, target: bool) {
+        use luajit::{OpCode, OperandMode as M};
+
+        let pc = pc as i64 + 1;
+        let [ma, mb, mc] = i.op.modes();
+        let a = if ma == M::None {
+            String::new()
+        } else {
+            i.a.to_string()
+        };
+        out!(
+            self,
+            "{pc:04} {} {:<6} {a:>3} ",
+            if target { "=>" } else { "  " },
+            i.op.name()
+        );
+
+        let mut d = i.d as i64;
+        if mc == M::Jump {
+            out!(self, "=> {:04}\n", pc + d - 0x7fff);
+            return;
+        }
+        if mb != M::None {
+            d &= 0xff;
+        } else if mc == M::None {
+            self.out.push('\n');
+            return;
+        }
+
+        let mut kc = match mc {
+            M::Str => match f.constants.get(d as usize) {
+                Some(LuaConstant::String(s)) => {
+                    let mut kc = vec![];
+                    for &c in s.iter() {
+                        match c {
+                            b'\n' => kc.extend_from_slice(b"\\n"),
+                            b'\r' => kc.extend_from_slice(b"\\r"),
+                            b'\t' => kc.extend_from_slice(b"\\t"),
+                            0..=0x1f | 0x7f => kc.extend(format!("\\{c:03}").bytes()),
+                            _ => kc.push(c),
+                        }
+                    }
+                    Some(if s.len() > 40 {
+                        format!("\"{}\"~", String::from_utf8_lossy(&kc[..40]))
+                    } else {
+                        format!("\"{}\"", String::from_utf8_lossy(&kc))
+                    })
+                }
+                _ => None,
+            },
+            M::Num => f.num_constants.get(d as usize).map(|n| {
+                let mut n = match *n {
+                    LuaNumber::Integer(n) => n as f64,
+                    LuaNumber::Float(n) => n,
+                };
+                // the base index of TSETM is stored with a 2^52 bias
+                if i.op == OpCode::TSetM {
+                    n -= (1u64 << 52) as f64;
+                }
+                number_g14(n)
+            }),
+            M::Func => match f.constants.get(d as usize) {
+                Some(LuaConstant::Proto(p)) => f.prototypes.get(*p).map(|p| self.loc(p)),
+                _ => None,
+            },
+            M::Uv => Self::upvalue_name(f, d as usize).map(Into::into),
+            _ => None,
+        };
+        if ma == M::Uv {
+            let ka = Self::upvalue_name(f, i.a as usize).unwrap_or_default();
+            kc = Some(match kc {
+                Some(kc) => format!("{ka} ; {kc}"),
+                None => ka.into(),
+            });
+        }
+
+        match kc {
+            Some(kc) if mb != M::None => {
+                out!(self, "{:3} {d:3}  ; {kc}\n", i.b());
+            }
+            None if mb != M::None => {
+                out!(self, "{:3} {d:3}\n", i.b());
+            }
+            Some(kc) => {
+                out!(self, "{d:3}      ; {kc}\n");
+            }
+            None => {
+                if mc == M::LitS && d > 32767 {
+                    d -= 65536;
+                }
+                out!(self, "{d:3}\n");
+            }
+        }
+    }
+}
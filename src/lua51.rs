@@ -20,7 +20,7 @@ pub fn lua_local<'a>(header: &LuaHeader) -> impl Parser<&'a [u8], LuaLocal, Erro
 pub fn lua_chunk<'h, 'a: 'h>(
     header: &'h LuaHeader,
 ) -> impl Parser<&'a [u8], LuaChunk, ErrorTree<&'a [u8]>> + 'h {
-    |input| {
+    limited(move |input| {
         let (input, name) = lua_string(header).parse(input)?;
         let (
             input,
@@ -41,22 +41,27 @@ pub fn lua_chunk<'h, 'a: 'h>(
                 })
                 .context("count instruction"),
                 length_count(lua_int(header).map(|x| x as usize), |input| {
-                    let (input, b) = be_u8(input)?;
+                    let (rest, b) = be_u8(input)?;
                     let result = match b {
-                        0 => success(LuaConstant::Null)(input),
-                        1 => map(be_u8, |v| LuaConstant::Bool(v != 0))(input),
-                        3 => map(lua_number(header), |v| LuaConstant::Number(v))(input),
-                        4 => map(lua_string(header), |v| LuaConstant::from(v.to_vec()))(input),
-                        _ => Err(nom::Err::Error(ErrorTree::from_char(
+                        0 => success(LuaConstant::Null)(rest),
+                        1 => map(be_u8, |v| LuaConstant::Bool(v != 0))(rest),
+                        3 => map(lua_number(header), |v| LuaConstant::Number(v))(rest),
+                        4 => map(lua_string(header), |v| LuaConstant::from(v.to_vec()))(rest),
+                        _ => fail_with(
                             input,
-                            char::from_digit(b as _, 10).unwrap_or('x'),
-                        ))),
+                            ParseError::InvalidConstant {
+                                offset: 0,
+                                tag: b,
+                                context: vec![],
+                                path: vec![],
+                            },
+                        ),
                     };
                     result
                 })
                 .context("count constants"),
                 |i| {
-                    length_count(lua_int(header).map(|x| x as usize), lua_chunk(header))
+                    length_protos(lua_int(header).map(|x| x as usize), lua_chunk(header))
                         .context("count prototypes")
                         .parse(i)
                 },
@@ -81,6 +86,7 @@ pub fn lua_chunk<'h, 'a: 'h>(
                     num_upvalues,
                     num_params,
                     flags: 0,
+                    type_info: None,
                     is_vararg: if (is_vararg & 2) != 0 {
                         Some(LuaVarArgInfo {
                             has_arg: (is_vararg & 1) != 0,
@@ -98,10 +104,516 @@ pub fn lua_chunk<'h, 'a: 'h>(
                     locals,
                     upvalue_names,
                     upvalue_infos: vec![],
+                    line_info: vec![],
                 }
             },
         )
         .context("chunk")
         .parse(input)
+    })
+}
+
+/// Inverse of `lua_string`
+pub fn dump_string(d: &mut DumpState, s: &[u8]) -> Result<(), DumpError> {
+    d.size_t(s.len() as u64 + 1)?;
+    d.bytes(s);
+    d.byte(0);
+    Ok(())
+}
+
+/// Source name of a chunk, the only string dumped as NULL when empty, as nested and stripped
+/// chunks have no source
+pub fn dump_source(d: &mut DumpState, s: &[u8]) -> Result<(), DumpError> {
+    if s.is_empty() {
+        return d.size_t(0);
+    }
+    dump_string(d, s)
+}
+
+/// Constant of lua51 and lua52
+pub(crate) fn dump_constant(d: &mut DumpState, k: &LuaConstant) -> Result<(), DumpError> {
+    match k {
+        LuaConstant::Null => d.byte(0),
+        LuaConstant::Bool(b) => d.bytes(&[1, *b as u8]),
+        LuaConstant::Number(n) => {
+            d.byte(3);
+            d.number(n)?;
+        }
+        LuaConstant::String(s) => {
+            d.byte(4);
+            dump_string(d, s)?;
+        }
+        _ => return Err(DumpError::UnsupportedConstant),
+    }
+    Ok(())
+}
+
+/// Debug info of lua51 and lua52 after the source name
+pub(crate) fn dump_debug(d: &mut DumpState, f: &LuaChunk) -> Result<(), DumpError> {
+    d.count(f.source_lines.len())?;
+    for &(line, _) in &f.source_lines {
+        d.int(line as _)?;
+    }
+    d.count(f.locals.len())?;
+    for l in &f.locals {
+        dump_string(d, l.name.as_bytes())?;
+        d.int(l.start_pc)?;
+        d.int(l.end_pc)?;
+    }
+    d.count(f.upvalue_names.len())?;
+    for name in &f.upvalue_names {
+        dump_string(d, name)?;
+    }
+    Ok(())
+}
+
+/// Inverse of `lua_chunk`
+pub fn dump_chunk(d: &mut DumpState, f: &LuaChunk) -> Result<(), DumpError> {
+    dump_source(d, &f.name)?;
+    d.int(f.line_defined)?;
+    d.int(f.last_line_defined)?;
+    d.bytes(&[
+        f.num_upvalues,
+        f.num_params,
+        f.is_vararg
+            .as_ref()
+            .map_or(0, |v| 2 | v.has_arg as u8 | (v.needs_arg as u8) << 2),
+        f.max_stack,
+    ]);
+    d.count(f.instructions.len())?;
+    for &i in &f.instructions {
+        d.instruction(i)?;
+    }
+    d.count(f.constants.len())?;
+    for k in &f.constants {
+        dump_constant(d, k)?;
+    }
+    d.count(f.prototypes.len())?;
+    for p in &f.prototypes {
+        dump_chunk(d, p)?;
+    }
+    dump_debug(d, f)
+}
+
+pub const SIZE_OP: u32 = 6;
+pub const SIZE_A: u32 = 8;
+pub const SIZE_B: u32 = 9;
+pub const SIZE_C: u32 = 9;
+pub const SIZE_BX: u32 = SIZE_B + SIZE_C;
+
+pub const POS_OP: u32 = 0;
+pub const POS_A: u32 = POS_OP + SIZE_OP;
+pub const POS_C: u32 = POS_A + SIZE_A;
+pub const POS_B: u32 = POS_C + SIZE_C;
+pub const POS_BX: u32 = POS_C;
+
+pub const MAXARG_BX: u32 = (1 << SIZE_BX) - 1;
+pub const MAXARG_SBX: i32 = (MAXARG_BX >> 1) as i32;
+
+/// Flag bit of B/C operands that marks a constant index, also used by lua52 and lua53
+pub const BITRK: u16 = 1 << (SIZE_B - 1);
+
+/// Number of list items to accumulate before a SETLIST instruction
+pub const LFIELDS_PER_FLUSH: u32 = 50;
+
+#[inline]
+pub fn get_op(i: u32) -> u8 {
+    ((i >> POS_OP) & ((1 << SIZE_OP) - 1)) as u8
+}
+
+#[inline]
+pub fn get_a(i: u32) -> u8 {
+    ((i >> POS_A) & ((1 << SIZE_A) - 1)) as u8
+}
+
+#[inline]
+pub fn get_b(i: u32) -> u16 {
+    ((i >> POS_B) & ((1 << SIZE_B) - 1)) as u16
+}
+
+#[inline]
+pub fn get_c(i: u32) -> u16 {
+    ((i >> POS_C) & ((1 << SIZE_C) - 1)) as u16
+}
+
+#[inline]
+pub fn get_bx(i: u32) -> u32 {
+    (i >> POS_BX) & MAXARG_BX
+}
+
+#[inline]
+pub fn get_sbx(i: u32) -> i32 {
+    get_bx(i) as i32 - MAXARG_SBX
+}
+
+/// A B/C operand which refers either to a register or to a constant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RK {
+    Register(u16),
+    Constant(u16),
+}
+
+impl RK {
+    pub fn new(x: u16) -> Self {
+        if x & BITRK != 0 {
+            Self::Constant(x & !BITRK)
+        } else {
+            Self::Register(x)
+        }
+    }
+
+    pub fn is_constant(self) -> bool {
+        matches!(self, Self::Constant(_))
+    }
+
+    /// The operand in `luac -l` notation, constants are printed as `-1 - index`
+    pub fn to_isk(self) -> i32 {
+        match self {
+            Self::Register(r) => r as i32,
+            Self::Constant(k) => -1 - k as i32,
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpCode {
+    Move,
+    LoadK,
+    LoadBool,
+    LoadNil,
+    GetUpval,
+    GetGlobal,
+    GetTable,
+    SetGlobal,
+    SetUpval,
+    SetTable,
+    NewTable,
+    Self_,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Unm,
+    Not,
+    Len,
+    Concat,
+    Jmp,
+    Eq,
+    Lt,
+    Le,
+    Test,
+    TestSet,
+    Call,
+    TailCall,
+    Return,
+    ForLoop,
+    ForPrep,
+    TForLoop,
+    SetList,
+    Close,
+    Closure,
+    VarArg,
+}
+
+impl OpCode {
+    pub const NAMES: [&'static str; 38] = [
+        "MOVE",
+        "LOADK",
+        "LOADBOOL",
+        "LOADNIL",
+        "GETUPVAL",
+        "GETGLOBAL",
+        "GETTABLE",
+        "SETGLOBAL",
+        "SETUPVAL",
+        "SETTABLE",
+        "NEWTABLE",
+        "SELF",
+        "ADD",
+        "SUB",
+        "MUL",
+        "DIV",
+        "MOD",
+        "POW",
+        "UNM",
+        "NOT",
+        "LEN",
+        "CONCAT",
+        "JMP",
+        "EQ",
+        "LT",
+        "LE",
+        "TEST",
+        "TESTSET",
+        "CALL",
+        "TAILCALL",
+        "RETURN",
+        "FORLOOP",
+        "FORPREP",
+        "TFORLOOP",
+        "SETLIST",
+        "CLOSE",
+        "CLOSURE",
+        "VARARG",
+    ];
+
+    pub fn from_u8(op: u8) -> Option<Self> {
+        if (op as usize) < Self::NAMES.len() {
+            Some(unsafe { core::mem::transmute::<u8, Self>(op) })
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+
+    /// Format and B/C operand usage of the opcode, as `luaP_opmodes` of lopcodes.c
+    pub fn modes(self) -> (OpMode, OpArgMask, OpArgMask) {
+        use OpArgMask::*;
+        use OpCode::*;
+
+        match self {
+            Move | LoadNil | Unm | Not | Len => (OpMode::ABC, R, N),
+            LoadK | GetGlobal | SetGlobal => (OpMode::ABx, K, N),
+            LoadBool | NewTable | Call | TailCall | SetList => (OpMode::ABC, U, U),
+            GetUpval | SetUpval | Return | VarArg => (OpMode::ABC, U, N),
+            GetTable | Self_ => (OpMode::ABC, R, K),
+            SetTable | Add | Sub | Mul | Div | Mod | Pow | Eq | Lt | Le => (OpMode::ABC, K, K),
+            Concat => (OpMode::ABC, R, R),
+            Jmp | ForLoop | ForPrep => (OpMode::AsBx, R, N),
+            Test | TestSet => (OpMode::ABC, R, U),
+            TForLoop => (OpMode::ABC, N, U),
+            Close => (OpMode::ABC, N, N),
+            Closure => (OpMode::ABx, U, N),
+        }
+    }
+}
+
+/// Instruction format of an opcode, also used by lua52 and lua53
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpMode {
+    ABC,
+    ABx,
+    AsBx,
+    Ax,
+}
+
+/// Usage of the B/C operand of an opcode, also used by lua52 and lua53
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpArgMask {
+    /// Not used
+    N,
+    /// Used
+    U,
+    /// A register or a jump offset
+    R,
+    /// A constant or a register/constant
+    K,
+}
+
+/// Decoded lua51 instruction, the operand names follow lopcodes.h
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// R(A) := R(B)
+    Move { a: u8, b: u16 },
+    /// R(A) := Kst(Bx)
+    LoadK { a: u8, bx: u32 },
+    /// R(A) := (Bool)B; if (C) pc++
+    LoadBool { a: u8, b: u16, c: u16 },
+    /// R(A) := ... := R(B) := nil
+    LoadNil { a: u8, b: u16 },
+    /// R(A) := UpValue[B]
+    GetUpval { a: u8, b: u16 },
+    /// R(A) := Gbl[Kst(Bx)]
+    GetGlobal { a: u8, bx: u32 },
+    /// R(A) := R(B)[RK(C)]
+    GetTable { a: u8, b: u16, c: RK },
+    /// Gbl[Kst(Bx)] := R(A)
+    SetGlobal { a: u8, bx: u32 },
+    /// UpValue[B] := R(A)
+    SetUpval { a: u8, b: u16 },
+    /// R(A)[RK(B)] := RK(C)
+    SetTable { a: u8, b: RK, c: RK },
+    /// R(A) := {} (size = B,C)
+    NewTable { a: u8, b: u16, c: u16 },
+    /// R(A+1) := R(B); R(A) := R(B)[RK(C)]
+    Self_ { a: u8, b: u16, c: RK },
+    /// R(A) := RK(B) + RK(C)
+    Add { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) - RK(C)
+    Sub { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) * RK(C)
+    Mul { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) / RK(C)
+    Div { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) % RK(C)
+    Mod { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) ^ RK(C)
+    Pow { a: u8, b: RK, c: RK },
+    /// R(A) := -R(B)
+    Unm { a: u8, b: u16 },
+    /// R(A) := not R(B)
+    Not { a: u8, b: u16 },
+    /// R(A) := length of R(B)
+    Len { a: u8, b: u16 },
+    /// R(A) := R(B).. ... ..R(C)
+    Concat { a: u8, b: u16, c: u16 },
+    /// pc+=sBx
+    Jmp { sbx: i32 },
+    /// if ((RK(B) == RK(C)) ~= A) then pc++
+    Eq { a: u8, b: RK, c: RK },
+    /// if ((RK(B) <  RK(C)) ~= A) then pc++
+    Lt { a: u8, b: RK, c: RK },
+    /// if ((RK(B) <= RK(C)) ~= A) then pc++
+    Le { a: u8, b: RK, c: RK },
+    /// if not (R(A) <=> C) then pc++
+    Test { a: u8, c: u16 },
+    /// if (R(B) <=> C) then R(A) := R(B) else pc++
+    TestSet { a: u8, b: u16, c: u16 },
+    /// R(A), ... ,R(A+C-2) := R(A)(R(A+1), ... ,R(A+B-1))
+    Call { a: u8, b: u16, c: u16 },
+    /// return R(A)(R(A+1), ... ,R(A+B-1))
+    TailCall { a: u8, b: u16, c: u16 },
+    /// return R(A), ... ,R(A+B-2)
+    Return { a: u8, b: u16 },
+    /// R(A)+=R(A+2); if R(A) <?= R(A+1) then { pc+=sBx; R(A+3)=R(A) }
+    ForLoop { a: u8, sbx: i32 },
+    /// R(A)-=R(A+2); pc+=sBx
+    ForPrep { a: u8, sbx: i32 },
+    /// R(A+3), ... ,R(A+2+C) := R(A)(R(A+1), R(A+2)); if R(A+3) ~= nil then R(A+2)=R(A+3) else pc++
+    TForLoop { a: u8, c: u16 },
+    /// R(A)[(C-1)*FPF+i] := R(A+i), 1 <= i <= B
+    SetList { a: u8, b: u16, c: u32 },
+    /// close all variables in the stack up to (>=) R(A)
+    Close { a: u8 },
+    /// R(A) := closure(KPROTO[Bx], R(A), ... ,R(A+n))
+    Closure { a: u8, bx: u32 },
+    /// R(A), R(A+1), ..., R(A+B-1) = vararg
+    VarArg { a: u8, b: u16 },
+}
+
+impl Instruction {
+    pub fn decode(i: u32) -> Option<Self> {
+        use Instruction::*;
+
+        let (a, b, c, bx, sbx) = (get_a(i), get_b(i), get_c(i), get_bx(i), get_sbx(i));
+        let (rb, rc) = (RK::new(b), RK::new(c));
+        Some(match OpCode::from_u8(get_op(i))? {
+            OpCode::Move => Move { a, b },
+            OpCode::LoadK => LoadK { a, bx },
+            OpCode::LoadBool => LoadBool { a, b, c },
+            OpCode::LoadNil => LoadNil { a, b },
+            OpCode::GetUpval => GetUpval { a, b },
+            OpCode::GetGlobal => GetGlobal { a, bx },
+            OpCode::GetTable => GetTable { a, b, c: rc },
+            OpCode::SetGlobal => SetGlobal { a, bx },
+            OpCode::SetUpval => SetUpval { a, b },
+            OpCode::SetTable => SetTable { a, b: rb, c: rc },
+            OpCode::NewTable => NewTable { a, b, c },
+            OpCode::Self_ => Self_ { a, b, c: rc },
+            OpCode::Add => Add { a, b: rb, c: rc },
+            OpCode::Sub => Sub { a, b: rb, c: rc },
+            OpCode::Mul => Mul { a, b: rb, c: rc },
+            OpCode::Div => Div { a, b: rb, c: rc },
+            OpCode::Mod => Mod { a, b: rb, c: rc },
+            OpCode::Pow => Pow { a, b: rb, c: rc },
+            OpCode::Unm => Unm { a, b },
+            OpCode::Not => Not { a, b },
+            OpCode::Len => Len { a, b },
+            OpCode::Concat => Concat { a, b, c },
+            OpCode::Jmp => Jmp { sbx },
+            OpCode::Eq => Eq { a, b: rb, c: rc },
+            OpCode::Lt => Lt { a, b: rb, c: rc },
+            OpCode::Le => Le { a, b: rb, c: rc },
+            OpCode::Test => Test { a, c },
+            OpCode::TestSet => TestSet { a, b, c },
+            OpCode::Call => Call { a, b, c },
+            OpCode::TailCall => TailCall { a, b, c },
+            OpCode::Return => Return { a, b },
+            OpCode::ForLoop => ForLoop { a, sbx },
+            OpCode::ForPrep => ForPrep { a, sbx },
+            OpCode::TForLoop => TForLoop { a, c },
+            OpCode::SetList => SetList { a, b, c: c as _ },
+            OpCode::Close => Close { a },
+            OpCode::Closure => Closure { a, bx },
+            OpCode::VarArg => VarArg { a, b },
+        })
+    }
+
+    pub fn opcode(&self) -> OpCode {
+        use Instruction::*;
+
+        match self {
+            Move { .. } => OpCode::Move,
+            LoadK { .. } => OpCode::LoadK,
+            LoadBool { .. } => OpCode::LoadBool,
+            LoadNil { .. } => OpCode::LoadNil,
+            GetUpval { .. } => OpCode::GetUpval,
+            GetGlobal { .. } => OpCode::GetGlobal,
+            GetTable { .. } => OpCode::GetTable,
+            SetGlobal { .. } => OpCode::SetGlobal,
+            SetUpval { .. } => OpCode::SetUpval,
+            SetTable { .. } => OpCode::SetTable,
+            NewTable { .. } => OpCode::NewTable,
+            Self_ { .. } => OpCode::Self_,
+            Add { .. } => OpCode::Add,
+            Sub { .. } => OpCode::Sub,
+            Mul { .. } => OpCode::Mul,
+            Div { .. } => OpCode::Div,
+            Mod { .. } => OpCode::Mod,
+            Pow { .. } => OpCode::Pow,
+            Unm { .. } => OpCode::Unm,
+            Not { .. } => OpCode::Not,
+            Len { .. } => OpCode::Len,
+            Concat { .. } => OpCode::Concat,
+            Jmp { .. } => OpCode::Jmp,
+            Eq { .. } => OpCode::Eq,
+            Lt { .. } => OpCode::Lt,
+            Le { .. } => OpCode::Le,
+            Test { .. } => OpCode::Test,
+            TestSet { .. } => OpCode::TestSet,
+            Call { .. } => OpCode::Call,
+            TailCall { .. } => OpCode::TailCall,
+            Return { .. } => OpCode::Return,
+            ForLoop { .. } => OpCode::ForLoop,
+            ForPrep { .. } => OpCode::ForPrep,
+            TForLoop { .. } => OpCode::TForLoop,
+            SetList { .. } => OpCode::SetList,
+            Close { .. } => OpCode::Close,
+            Closure { .. } => OpCode::Closure,
+            VarArg { .. } => OpCode::VarArg,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.opcode().name()
+    }
+}
+
+/// Decode all instructions of a chunk into `(pc, instruction)` pairs.
+///
+/// A SETLIST with C == 0 takes the real C from the next word, which is consumed along with it
+pub fn decode(chunk: &LuaChunk) -> Result<Vec<(usize, Instruction)>, DecodeError> {
+    let code = &chunk.instructions;
+    let mut result = Vec::with_capacity(code.len());
+    let mut pc = 0;
+    while pc < code.len() {
+        let raw = code[pc];
+        let mut insn = Instruction::decode(raw).ok_or(DecodeError::InvalidOpcode { pc, raw })?;
+        let size = match &mut insn {
+            Instruction::SetList { c, .. } if *c == 0 => {
+                *c = *code.get(pc + 1).ok_or(DecodeError::Truncated { pc })?;
+                2
+            }
+            _ => 1,
+        };
+        result.push((pc, insn));
+        pc += size;
     }
+    Ok(result)
 }
@@ -16,7 +16,7 @@ pub fn load_upvalue(input: &[u8]) -> IResult<&[u8], UpVal> {
 pub fn lua_chunk<'h, 'a: 'h>(
     header: &'h LuaHeader,
 ) -> impl Parser<&'a [u8], LuaChunk, ErrorTree<&'a [u8]>> + 'h {
-    |input| {
+    limited(move |input| {
         let (input, (line_defined, last_line_defined, num_params, is_vararg, max_stack)) =
             tuple((lua_int(header), lua_int(header), be_u8, be_u8, be_u8))(input)?;
         log::trace!("chunk: \"\", line: {line_defined}-{last_line_defined}",);
@@ -31,22 +31,27 @@ pub fn lua_chunk<'h, 'a: 'h>(
                 })
                 .context("count instruction"),
                 length_count(lua_int(header).map(|x| x as usize), |input| {
-                    let (input, b) = be_u8(input)?;
+                    let (rest, b) = be_u8(input)?;
                     let result = match b {
-                        0 => success(LuaConstant::Null)(input),
-                        1 => map(be_u8, |v| LuaConstant::Bool(v != 0))(input),
-                        3 => map(lua_number(header), |v| LuaConstant::Number(v))(input),
-                        4 => map(lua_string(header), |v| LuaConstant::from(v.to_vec()))(input),
-                        _ => Err(nom::Err::Error(ErrorTree::from_char(
+                        0 => success(LuaConstant::Null)(rest),
+                        1 => map(be_u8, |v| LuaConstant::Bool(v != 0))(rest),
+                        3 => map(lua_number(header), |v| LuaConstant::Number(v))(rest),
+                        4 => map(lua_string(header), |v| LuaConstant::from(v.to_vec()))(rest),
+                        _ => fail_with(
                             input,
-                            char::from_digit(b as _, 10).unwrap_or('x'),
-                        ))),
+                            ParseError::InvalidConstant {
+                                offset: 0,
+                                tag: b,
+                                context: vec![],
+                                path: vec![],
+                            },
+                        ),
                     };
                     result
                 })
                 .context("count constants"),
                 |i| {
-                    length_count(lua_int(header).map(|x| x as usize), lua_chunk(header))
+                    length_protos(lua_int(header).map(|x| x as usize), lua_chunk(header))
                         .context("count prototypes")
                         .parse(i)
                 },
@@ -83,6 +88,7 @@ pub fn lua_chunk<'h, 'a: 'h>(
                     num_upvalues: upvalue_infos.len() as _,
                     num_params,
                     flags: 0,
+                    type_info: None,
                     is_vararg: if is_vararg != 0 {
                         Some(LuaVarArgInfo::new())
                     } else {
@@ -97,10 +103,366 @@ pub fn lua_chunk<'h, 'a: 'h>(
                     locals,
                     upvalue_names,
                     upvalue_infos,
+                    line_info: vec![],
                 }
             },
         )
         .context("chunk")
         .parse(input)
+    })
+}
+
+/// Inverse of `lua_chunk`
+pub fn dump_chunk(d: &mut DumpState, f: &LuaChunk) -> Result<(), DumpError> {
+    d.int(f.line_defined)?;
+    d.int(f.last_line_defined)?;
+    d.bytes(&[f.num_params, f.is_vararg.is_some() as u8, f.max_stack]);
+    d.count(f.instructions.len())?;
+    for &i in &f.instructions {
+        d.instruction(i)?;
+    }
+    d.count(f.constants.len())?;
+    for k in &f.constants {
+        lua51::dump_constant(d, k)?;
+    }
+    d.count(f.prototypes.len())?;
+    for p in &f.prototypes {
+        dump_chunk(d, p)?;
+    }
+    d.count(f.upvalue_infos.len())?;
+    for u in &f.upvalue_infos {
+        d.bytes(&[u.on_stack as u8, u.id]);
+    }
+    lua51::dump_source(d, &f.name)?;
+    lua51::dump_debug(d, f)
+}
+
+pub use super::lua51::{
+    get_a, get_b, get_bx, get_c, get_op, get_sbx, OpArgMask, OpMode, BITRK, RK,
+};
+
+pub const SIZE_AX: u32 = 26;
+pub const POS_AX: u32 = 6;
+pub const MAXARG_AX: u32 = (1 << SIZE_AX) - 1;
+
+#[inline]
+pub fn get_ax(i: u32) -> u32 {
+    (i >> POS_AX) & MAXARG_AX
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpCode {
+    Move,
+    LoadK,
+    LoadKX,
+    LoadBool,
+    LoadNil,
+    GetUpval,
+    GetTabUp,
+    GetTable,
+    SetTabUp,
+    SetUpval,
+    SetTable,
+    NewTable,
+    Self_,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Unm,
+    Not,
+    Len,
+    Concat,
+    Jmp,
+    Eq,
+    Lt,
+    Le,
+    Test,
+    TestSet,
+    Call,
+    TailCall,
+    Return,
+    ForLoop,
+    ForPrep,
+    TForCall,
+    TForLoop,
+    SetList,
+    Closure,
+    VarArg,
+    ExtraArg,
+}
+
+impl OpCode {
+    pub const NAMES: [&'static str; 40] = [
+        "MOVE", "LOADK", "LOADKX", "LOADBOOL", "LOADNIL", "GETUPVAL", "GETTABUP", "GETTABLE",
+        "SETTABUP", "SETUPVAL", "SETTABLE", "NEWTABLE", "SELF", "ADD", "SUB", "MUL", "DIV", "MOD",
+        "POW", "UNM", "NOT", "LEN", "CONCAT", "JMP", "EQ", "LT", "LE", "TEST", "TESTSET", "CALL",
+        "TAILCALL", "RETURN", "FORLOOP", "FORPREP", "TFORCALL", "TFORLOOP", "SETLIST", "CLOSURE",
+        "VARARG", "EXTRAARG",
+    ];
+
+    pub fn from_u8(op: u8) -> Option<Self> {
+        if (op as usize) < Self::NAMES.len() {
+            Some(unsafe { core::mem::transmute::<u8, Self>(op) })
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+
+    /// Format and B/C operand usage of the opcode, as `luaP_opmodes` of lopcodes.c
+    pub fn modes(self) -> (OpMode, OpArgMask, OpArgMask) {
+        use OpArgMask::*;
+        use OpCode::*;
+
+        match self {
+            Move | Unm | Not | Len => (OpMode::ABC, R, N),
+            LoadK => (OpMode::ABx, K, N),
+            LoadKX => (OpMode::ABx, N, N),
+            LoadBool | NewTable | Call | TailCall | SetList => (OpMode::ABC, U, U),
+            LoadNil | GetUpval | SetUpval | Return | VarArg => (OpMode::ABC, U, N),
+            GetTabUp => (OpMode::ABC, U, K),
+            GetTable | Self_ => (OpMode::ABC, R, K),
+            SetTabUp | SetTable | Add | Sub | Mul | Div | Mod | Pow | Eq | Lt | Le => {
+                (OpMode::ABC, K, K)
+            }
+            Concat => (OpMode::ABC, R, R),
+            Jmp | ForLoop | ForPrep | TForLoop => (OpMode::AsBx, R, N),
+            Test | TForCall => (OpMode::ABC, N, U),
+            TestSet => (OpMode::ABC, R, U),
+            Closure => (OpMode::ABx, U, N),
+            ExtraArg => (OpMode::Ax, U, U),
+        }
+    }
+}
+
+/// Decoded lua52 instruction, the operand names follow lopcodes.h
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// R(A) := R(B)
+    Move { a: u8, b: u16 },
+    /// R(A) := Kst(Bx)
+    LoadK { a: u8, bx: u32 },
+    /// R(A) := Kst(extra arg)
+    LoadKX { a: u8, ax: u32 },
+    /// R(A) := (Bool)B; if (C) pc++
+    LoadBool { a: u8, b: u16, c: u16 },
+    /// R(A), R(A+1), ..., R(A+B) := nil
+    LoadNil { a: u8, b: u16 },
+    /// R(A) := UpValue[B]
+    GetUpval { a: u8, b: u16 },
+    /// R(A) := UpValue[B][RK(C)]
+    GetTabUp { a: u8, b: u16, c: RK },
+    /// R(A) := R(B)[RK(C)]
+    GetTable { a: u8, b: u16, c: RK },
+    /// UpValue[A][RK(B)] := RK(C)
+    SetTabUp { a: u8, b: RK, c: RK },
+    /// UpValue[B] := R(A)
+    SetUpval { a: u8, b: u16 },
+    /// R(A)[RK(B)] := RK(C)
+    SetTable { a: u8, b: RK, c: RK },
+    /// R(A) := {} (size = B,C)
+    NewTable { a: u8, b: u16, c: u16 },
+    /// R(A+1) := R(B); R(A) := R(B)[RK(C)]
+    Self_ { a: u8, b: u16, c: RK },
+    /// R(A) := RK(B) + RK(C)
+    Add { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) - RK(C)
+    Sub { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) * RK(C)
+    Mul { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) / RK(C)
+    Div { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) % RK(C)
+    Mod { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) ^ RK(C)
+    Pow { a: u8, b: RK, c: RK },
+    /// R(A) := -R(B)
+    Unm { a: u8, b: u16 },
+    /// R(A) := not R(B)
+    Not { a: u8, b: u16 },
+    /// R(A) := length of R(B)
+    Len { a: u8, b: u16 },
+    /// R(A) := R(B).. ... ..R(C)
+    Concat { a: u8, b: u16, c: u16 },
+    /// pc+=sBx; if (A) close all upvalues >= R(A - 1)
+    Jmp { a: u8, sbx: i32 },
+    /// if ((RK(B) == RK(C)) ~= A) then pc++
+    Eq { a: u8, b: RK, c: RK },
+    /// if ((RK(B) <  RK(C)) ~= A) then pc++
+    Lt { a: u8, b: RK, c: RK },
+    /// if ((RK(B) <= RK(C)) ~= A) then pc++
+    Le { a: u8, b: RK, c: RK },
+    /// if not (R(A) <=> C) then pc++
+    Test { a: u8, c: u16 },
+    /// if (R(B) <=> C) then R(A) := R(B) else pc++
+    TestSet { a: u8, b: u16, c: u16 },
+    /// R(A), ... ,R(A+C-2) := R(A)(R(A+1), ... ,R(A+B-1))
+    Call { a: u8, b: u16, c: u16 },
+    /// return R(A)(R(A+1), ... ,R(A+B-1))
+    TailCall { a: u8, b: u16, c: u16 },
+    /// return R(A), ... ,R(A+B-2)
+    Return { a: u8, b: u16 },
+    /// R(A)+=R(A+2); if R(A) <?= R(A+1) then { pc+=sBx; R(A+3)=R(A) }
+    ForLoop { a: u8, sbx: i32 },
+    /// R(A)-=R(A+2); pc+=sBx
+    ForPrep { a: u8, sbx: i32 },
+    /// R(A+3), ... ,R(A+2+C) := R(A)(R(A+1), R(A+2))
+    TForCall { a: u8, c: u16 },
+    /// if R(A+1) ~= nil then { R(A)=R(A+1); pc += sBx }
+    TForLoop { a: u8, sbx: i32 },
+    /// R(A)[(C-1)*FPF+i] := R(A+i), 1 <= i <= B
+    SetList { a: u8, b: u16, c: u32 },
+    /// R(A) := closure(KPROTO[Bx])
+    Closure { a: u8, bx: u32 },
+    /// R(A), R(A+1), ..., R(A+B-2) = vararg
+    VarArg { a: u8, b: u16 },
+    /// extra (larger) argument for previous opcode
+    ExtraArg { ax: u32 },
+}
+
+impl Instruction {
+    /// Decode a single word, LOADKX and SETLIST with C == 0 need the following
+    /// EXTRAARG which is paired by [`decode`]
+    pub fn decode(i: u32) -> Option<Self> {
+        use Instruction::*;
+
+        let (a, b, c, bx, sbx) = (get_a(i), get_b(i), get_c(i), get_bx(i), get_sbx(i));
+        let (rb, rc) = (RK::new(b), RK::new(c));
+        Some(match OpCode::from_u8(get_op(i))? {
+            OpCode::Move => Move { a, b },
+            OpCode::LoadK => LoadK { a, bx },
+            OpCode::LoadKX => LoadKX { a, ax: 0 },
+            OpCode::LoadBool => LoadBool { a, b, c },
+            OpCode::LoadNil => LoadNil { a, b },
+            OpCode::GetUpval => GetUpval { a, b },
+            OpCode::GetTabUp => GetTabUp { a, b, c: rc },
+            OpCode::GetTable => GetTable { a, b, c: rc },
+            OpCode::SetTabUp => SetTabUp { a, b: rb, c: rc },
+            OpCode::SetUpval => SetUpval { a, b },
+            OpCode::SetTable => SetTable { a, b: rb, c: rc },
+            OpCode::NewTable => NewTable { a, b, c },
+            OpCode::Self_ => Self_ { a, b, c: rc },
+            OpCode::Add => Add { a, b: rb, c: rc },
+            OpCode::Sub => Sub { a, b: rb, c: rc },
+            OpCode::Mul => Mul { a, b: rb, c: rc },
+            OpCode::Div => Div { a, b: rb, c: rc },
+            OpCode::Mod => Mod { a, b: rb, c: rc },
+            OpCode::Pow => Pow { a, b: rb, c: rc },
+            OpCode::Unm => Unm { a, b },
+            OpCode::Not => Not { a, b },
+            OpCode::Len => Len { a, b },
+            OpCode::Concat => Concat { a, b, c },
+            OpCode::Jmp => Jmp { a, sbx },
+            OpCode::Eq => Eq { a, b: rb, c: rc },
+            OpCode::Lt => Lt { a, b: rb, c: rc },
+            OpCode::Le => Le { a, b: rb, c: rc },
+            OpCode::Test => Test { a, c },
+            OpCode::TestSet => TestSet { a, b, c },
+            OpCode::Call => Call { a, b, c },
+            OpCode::TailCall => TailCall { a, b, c },
+            OpCode::Return => Return { a, b },
+            OpCode::ForLoop => ForLoop { a, sbx },
+            OpCode::ForPrep => ForPrep { a, sbx },
+            OpCode::TForCall => TForCall { a, c },
+            OpCode::TForLoop => TForLoop { a, sbx },
+            OpCode::SetList => SetList { a, b, c: c as _ },
+            OpCode::Closure => Closure { a, bx },
+            OpCode::VarArg => VarArg { a, b },
+            OpCode::ExtraArg => ExtraArg { ax: get_ax(i) },
+        })
+    }
+
+    pub fn opcode(&self) -> OpCode {
+        use Instruction::*;
+
+        match self {
+            Move { .. } => OpCode::Move,
+            LoadK { .. } => OpCode::LoadK,
+            LoadKX { .. } => OpCode::LoadKX,
+            LoadBool { .. } => OpCode::LoadBool,
+            LoadNil { .. } => OpCode::LoadNil,
+            GetUpval { .. } => OpCode::GetUpval,
+            GetTabUp { .. } => OpCode::GetTabUp,
+            GetTable { .. } => OpCode::GetTable,
+            SetTabUp { .. } => OpCode::SetTabUp,
+            SetUpval { .. } => OpCode::SetUpval,
+            SetTable { .. } => OpCode::SetTable,
+            NewTable { .. } => OpCode::NewTable,
+            Self_ { .. } => OpCode::Self_,
+            Add { .. } => OpCode::Add,
+            Sub { .. } => OpCode::Sub,
+            Mul { .. } => OpCode::Mul,
+            Div { .. } => OpCode::Div,
+            Mod { .. } => OpCode::Mod,
+            Pow { .. } => OpCode::Pow,
+            Unm { .. } => OpCode::Unm,
+            Not { .. } => OpCode::Not,
+            Len { .. } => OpCode::Len,
+            Concat { .. } => OpCode::Concat,
+            Jmp { .. } => OpCode::Jmp,
+            Eq { .. } => OpCode::Eq,
+            Lt { .. } => OpCode::Lt,
+            Le { .. } => OpCode::Le,
+            Test { .. } => OpCode::Test,
+            TestSet { .. } => OpCode::TestSet,
+            Call { .. } => OpCode::Call,
+            TailCall { .. } => OpCode::TailCall,
+            Return { .. } => OpCode::Return,
+            ForLoop { .. } => OpCode::ForLoop,
+            ForPrep { .. } => OpCode::ForPrep,
+            TForCall { .. } => OpCode::TForCall,
+            TForLoop { .. } => OpCode::TForLoop,
+            SetList { .. } => OpCode::SetList,
+            Closure { .. } => OpCode::Closure,
+            VarArg { .. } => OpCode::VarArg,
+            ExtraArg { .. } => OpCode::ExtraArg,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.opcode().name()
+    }
+}
+
+/// Take the Ax of the EXTRAARG at `pc`, shared by lua52 and lua53 which have the same iAx layout
+pub(crate) fn extra_arg(code: &[u32], pc: usize, extra_arg_op: u8) -> Result<u32, DecodeError> {
+    let raw = *code.get(pc).ok_or(DecodeError::Truncated { pc: pc - 1 })?;
+    if get_op(raw) != extra_arg_op {
+        return Err(DecodeError::InvalidOpcode { pc, raw });
+    }
+    Ok(get_ax(raw))
+}
+
+/// Decode all instructions of a chunk into `(pc, instruction)` pairs.
+///
+/// LOADKX and SETLIST with C == 0 are paired with their trailing EXTRAARG, which is consumed along with them
+pub fn decode(chunk: &LuaChunk) -> Result<Vec<(usize, Instruction)>, DecodeError> {
+    let code = &chunk.instructions;
+    let mut result = Vec::with_capacity(code.len());
+    let mut pc = 0;
+    while pc < code.len() {
+        let raw = code[pc];
+        let mut insn = Instruction::decode(raw).ok_or(DecodeError::InvalidOpcode { pc, raw })?;
+        let size = match &mut insn {
+            Instruction::LoadKX { ax, .. } => {
+                *ax = extra_arg(code, pc + 1, OpCode::ExtraArg as _)?;
+                2
+            }
+            Instruction::SetList { c, .. } if *c == 0 => {
+                *c = extra_arg(code, pc + 1, OpCode::ExtraArg as _)?;
+                2
+            }
+            _ => 1,
+        };
+        result.push((pc, insn));
+        pc += size;
     }
+    Ok(result)
 }
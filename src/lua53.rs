@@ -30,7 +30,7 @@ pub fn lua_local<'a>(header: &LuaHeader) -> impl Parser<&'a [u8], LuaLocal, Erro
 pub fn lua_chunk<'h, 'a: 'h>(
     header: &'h LuaHeader,
 ) -> impl Parser<&'a [u8], LuaChunk, ErrorTree<&'a [u8]>> + 'h {
-    |input| {
+    limited(move |input| {
         let (input, (name, line_defined, last_line_defined, num_params, is_vararg, max_stack)) =
             tuple((
                 load_string,
@@ -62,13 +62,14 @@ pub fn lua_chunk<'h, 'a: 'h>(
                         take_lv_float,
                         take_lv_str,
                         take_lv_u64,
+                        invalid_constant,
                     )),
                 )
                 .context("count constants"),
                 length_count(lua_int(header).map(|x| x as usize), load_upvalue)
                     .context("count upvalues"),
                 |i| {
-                    length_count(lua_int(header).map(|x| x as usize), lua_chunk(header))
+                    length_protos(lua_int(header).map(|x| x as usize), lua_chunk(header))
                         .context("count prototypes")
                         .parse(i)
                 },
@@ -101,6 +102,7 @@ pub fn lua_chunk<'h, 'a: 'h>(
                     num_upvalues: upvalue_infos.len() as _,
                     num_params,
                     flags: 0,
+                    type_info: None,
                     is_vararg: if is_vararg != 0 {
                         Some(LuaVarArgInfo::new())
                     } else {
@@ -115,12 +117,13 @@ pub fn lua_chunk<'h, 'a: 'h>(
                     upvalue_names,
                     upvalue_infos,
                     num_constants: vec![],
+                    line_info: vec![],
                 }
             },
         )
         .context("chunk")
         .parse(input)
-    }
+    })
 }
 
 fn take_lv_nil(input: &[u8]) -> IResult<&[u8], LuaConstant> {
@@ -138,12 +141,15 @@ fn take_lv_float(input: &[u8]) -> IResult<&[u8], LuaConstant> {
     Ok((input, LuaConstant::Number(LuaNumber::Float(f as _))))
 }
 
-fn le_u8_minus_one(input: &[u8]) -> IResult<&[u8], u8> {
+fn le_u8_minus_one(input: &[u8]) -> IResult<&[u8], u64> {
     let (mut input, out) = le_u8(input)?;
+    let mut out = out as u64;
     if out == 0xFF {
-        // TODO: usize
         (input, out) = complete::le_u64(input)?;
     }
+    if out == 0 {
+        return fail_expected(input, "string");
+    }
     Ok((input, out - 1))
 }
 
@@ -160,3 +166,444 @@ fn take_lv_u64(input: &[u8]) -> IResult<&[u8], LuaConstant> {
     let (input, (_, val)) = tuple((tag(b"\x13"), complete::le_u64))(input)?;
     Ok((input, LuaConstant::Number(LuaNumber::Integer(val as _))))
 }
+
+pub(crate) fn invalid_constant(input: &[u8]) -> IResult<&[u8], LuaConstant> {
+    let (_, tag) = le_u8(input)?;
+    fail_with(
+        input,
+        ParseError::InvalidConstant {
+            offset: 0,
+            tag,
+            context: vec![],
+            path: vec![],
+        },
+    )
+}
+
+/// Strings longer than this are dumped as long strings (`LUAI_MAXSHORTLEN`)
+pub const MAX_SHORT_LEN: usize = 40;
+
+/// Inverse of `load_string`
+pub fn dump_string(d: &mut DumpState, s: &[u8]) -> Result<(), DumpError> {
+    let size = s.len() as u64 + 1;
+    if size < 0xFF {
+        d.byte(size as u8);
+    } else {
+        d.byte(0xFF);
+        d.size_t(size)?;
+    }
+    d.bytes(s);
+    Ok(())
+}
+
+/// Source name of a chunk, see [`lua51::dump_source`]
+pub fn dump_source(d: &mut DumpState, s: &[u8]) -> Result<(), DumpError> {
+    if s.is_empty() {
+        d.byte(0);
+        return Ok(());
+    }
+    dump_string(d, s)
+}
+
+/// Inverse of `lua_chunk`
+pub fn dump_chunk(d: &mut DumpState, f: &LuaChunk) -> Result<(), DumpError> {
+    dump_source(d, &f.name)?;
+    d.int(f.line_defined)?;
+    d.int(f.last_line_defined)?;
+    d.bytes(&[f.num_params, f.is_vararg.is_some() as u8, f.max_stack]);
+    d.count(f.instructions.len())?;
+    for &i in &f.instructions {
+        d.instruction(i)?;
+    }
+    d.count(f.constants.len())?;
+    for k in &f.constants {
+        match k {
+            LuaConstant::Null => d.byte(0),
+            LuaConstant::Bool(b) => d.bytes(&[1, *b as u8]),
+            LuaConstant::Number(n @ LuaNumber::Float(_)) => {
+                d.byte(3);
+                d.number(n)?;
+            }
+            LuaConstant::Number(LuaNumber::Integer(i)) => {
+                d.byte(0x13);
+                d.uint(*i as u64, 8)?;
+            }
+            LuaConstant::String(s) => {
+                d.byte(if s.len() <= MAX_SHORT_LEN { 4 } else { 0x14 });
+                dump_string(d, s)?;
+            }
+            _ => return Err(DumpError::UnsupportedConstant),
+        }
+    }
+    d.count(f.upvalue_infos.len())?;
+    for u in &f.upvalue_infos {
+        d.bytes(&[u.on_stack as u8, u.id]);
+    }
+    d.count(f.prototypes.len())?;
+    for p in &f.prototypes {
+        dump_chunk(d, p)?;
+    }
+    d.count(f.source_lines.len())?;
+    for &(line, _) in &f.source_lines {
+        d.int(line as _)?;
+    }
+    d.count(f.locals.len())?;
+    for l in &f.locals {
+        dump_string(d, l.name.as_bytes())?;
+        d.int(l.start_pc)?;
+        d.int(l.end_pc)?;
+    }
+    d.count(f.upvalue_names.len())?;
+    for name in &f.upvalue_names {
+        dump_string(d, name)?;
+    }
+    Ok(())
+}
+
+pub use super::lua52::{
+    get_a, get_ax, get_b, get_bx, get_c, get_op, get_sbx, OpArgMask, OpMode, BITRK, RK,
+};
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpCode {
+    Move,
+    LoadK,
+    LoadKX,
+    LoadBool,
+    LoadNil,
+    GetUpval,
+    GetTabUp,
+    GetTable,
+    SetTabUp,
+    SetUpval,
+    SetTable,
+    NewTable,
+    Self_,
+    Add,
+    Sub,
+    Mul,
+    Mod,
+    Pow,
+    Div,
+    IDiv,
+    BAnd,
+    BOr,
+    BXor,
+    Shl,
+    Shr,
+    Unm,
+    BNot,
+    Not,
+    Len,
+    Concat,
+    Jmp,
+    Eq,
+    Lt,
+    Le,
+    Test,
+    TestSet,
+    Call,
+    TailCall,
+    Return,
+    ForLoop,
+    ForPrep,
+    TForCall,
+    TForLoop,
+    SetList,
+    Closure,
+    VarArg,
+    ExtraArg,
+}
+
+impl OpCode {
+    pub const NAMES: [&'static str; 47] = [
+        "MOVE", "LOADK", "LOADKX", "LOADBOOL", "LOADNIL", "GETUPVAL", "GETTABUP", "GETTABLE",
+        "SETTABUP", "SETUPVAL", "SETTABLE", "NEWTABLE", "SELF", "ADD", "SUB", "MUL", "MOD", "POW",
+        "DIV", "IDIV", "BAND", "BOR", "BXOR", "SHL", "SHR", "UNM", "BNOT", "NOT", "LEN", "CONCAT",
+        "JMP", "EQ", "LT", "LE", "TEST", "TESTSET", "CALL", "TAILCALL", "RETURN", "FORLOOP",
+        "FORPREP", "TFORCALL", "TFORLOOP", "SETLIST", "CLOSURE", "VARARG", "EXTRAARG",
+    ];
+
+    pub fn from_u8(op: u8) -> Option<Self> {
+        if (op as usize) < Self::NAMES.len() {
+            Some(unsafe { core::mem::transmute::<u8, Self>(op) })
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+
+    /// Format and B/C operand usage of the opcode, as `luaP_opmodes` of lopcodes.c
+    pub fn modes(self) -> (OpMode, OpArgMask, OpArgMask) {
+        use OpArgMask::*;
+        use OpCode::*;
+
+        match self {
+            Move | Unm | BNot | Not | Len => (OpMode::ABC, R, N),
+            LoadK => (OpMode::ABx, K, N),
+            LoadKX => (OpMode::ABx, N, N),
+            LoadBool | NewTable | Call | TailCall | SetList => (OpMode::ABC, U, U),
+            LoadNil | GetUpval | SetUpval | Return | VarArg => (OpMode::ABC, U, N),
+            GetTabUp => (OpMode::ABC, U, K),
+            GetTable | Self_ => (OpMode::ABC, R, K),
+            SetTable | SetTabUp | Add | Sub | Mul | Mod | Pow | Div | IDiv | BAnd | BOr | BXor
+            | Shl | Shr | Eq | Lt | Le => (OpMode::ABC, K, K),
+            Concat => (OpMode::ABC, R, R),
+            Jmp | ForLoop | ForPrep | TForLoop => (OpMode::AsBx, R, N),
+            Test | TForCall => (OpMode::ABC, N, U),
+            TestSet => (OpMode::ABC, R, U),
+            Closure => (OpMode::ABx, U, N),
+            ExtraArg => (OpMode::Ax, U, U),
+        }
+    }
+}
+
+/// Decoded lua53 instruction, the operand names follow lopcodes.h
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// R(A) := R(B)
+    Move { a: u8, b: u16 },
+    /// R(A) := Kst(Bx)
+    LoadK { a: u8, bx: u32 },
+    /// R(A) := Kst(extra arg)
+    LoadKX { a: u8, ax: u32 },
+    /// R(A) := (Bool)B; if (C) pc++
+    LoadBool { a: u8, b: u16, c: u16 },
+    /// R(A), R(A+1), ..., R(A+B) := nil
+    LoadNil { a: u8, b: u16 },
+    /// R(A) := UpValue[B]
+    GetUpval { a: u8, b: u16 },
+    /// R(A) := UpValue[B][RK(C)]
+    GetTabUp { a: u8, b: u16, c: RK },
+    /// R(A) := R(B)[RK(C)]
+    GetTable { a: u8, b: u16, c: RK },
+    /// UpValue[A][RK(B)] := RK(C)
+    SetTabUp { a: u8, b: RK, c: RK },
+    /// UpValue[B] := R(A)
+    SetUpval { a: u8, b: u16 },
+    /// R(A)[RK(B)] := RK(C)
+    SetTable { a: u8, b: RK, c: RK },
+    /// R(A) := {} (size = B,C)
+    NewTable { a: u8, b: u16, c: u16 },
+    /// R(A+1) := R(B); R(A) := R(B)[RK(C)]
+    Self_ { a: u8, b: u16, c: RK },
+    /// R(A) := RK(B) + RK(C)
+    Add { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) - RK(C)
+    Sub { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) * RK(C)
+    Mul { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) % RK(C)
+    Mod { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) ^ RK(C)
+    Pow { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) / RK(C)
+    Div { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) // RK(C)
+    IDiv { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) & RK(C)
+    BAnd { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) | RK(C)
+    BOr { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) ~ RK(C)
+    BXor { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) << RK(C)
+    Shl { a: u8, b: RK, c: RK },
+    /// R(A) := RK(B) >> RK(C)
+    Shr { a: u8, b: RK, c: RK },
+    /// R(A) := -R(B)
+    Unm { a: u8, b: u16 },
+    /// R(A) := ~R(B)
+    BNot { a: u8, b: u16 },
+    /// R(A) := not R(B)
+    Not { a: u8, b: u16 },
+    /// R(A) := length of R(B)
+    Len { a: u8, b: u16 },
+    /// R(A) := R(B).. ... ..R(C)
+    Concat { a: u8, b: u16, c: u16 },
+    /// pc+=sBx; if (A) close all upvalues >= R(A - 1)
+    Jmp { a: u8, sbx: i32 },
+    /// if ((RK(B) == RK(C)) ~= A) then pc++
+    Eq { a: u8, b: RK, c: RK },
+    /// if ((RK(B) <  RK(C)) ~= A) then pc++
+    Lt { a: u8, b: RK, c: RK },
+    /// if ((RK(B) <= RK(C)) ~= A) then pc++
+    Le { a: u8, b: RK, c: RK },
+    /// if not (R(A) <=> C) then pc++
+    Test { a: u8, c: u16 },
+    /// if (R(B) <=> C) then R(A) := R(B) else pc++
+    TestSet { a: u8, b: u16, c: u16 },
+    /// R(A), ... ,R(A+C-2) := R(A)(R(A+1), ... ,R(A+B-1))
+    Call { a: u8, b: u16, c: u16 },
+    /// return R(A)(R(A+1), ... ,R(A+B-1))
+    TailCall { a: u8, b: u16, c: u16 },
+    /// return R(A), ... ,R(A+B-2)
+    Return { a: u8, b: u16 },
+    /// R(A)+=R(A+2); if R(A) <?= R(A+1) then { pc+=sBx; R(A+3)=R(A) }
+    ForLoop { a: u8, sbx: i32 },
+    /// R(A)-=R(A+2); pc+=sBx
+    ForPrep { a: u8, sbx: i32 },
+    /// R(A+3), ... ,R(A+2+C) := R(A)(R(A+1), R(A+2))
+    TForCall { a: u8, c: u16 },
+    /// if R(A+1) ~= nil then { R(A)=R(A+1); pc += sBx }
+    TForLoop { a: u8, sbx: i32 },
+    /// R(A)[(C-1)*FPF+i] := R(A+i), 1 <= i <= B
+    SetList { a: u8, b: u16, c: u32 },
+    /// R(A) := closure(KPROTO[Bx])
+    Closure { a: u8, bx: u32 },
+    /// R(A), R(A+1), ..., R(A+B-2) = vararg
+    VarArg { a: u8, b: u16 },
+    /// extra (larger) argument for previous opcode
+    ExtraArg { ax: u32 },
+}
+
+impl Instruction {
+    /// Decode a single word, LOADKX and SETLIST with C == 0 need the following
+    /// EXTRAARG which is paired by [`decode`]
+    pub fn decode(i: u32) -> Option<Self> {
+        use Instruction::*;
+
+        let (a, b, c, bx, sbx) = (get_a(i), get_b(i), get_c(i), get_bx(i), get_sbx(i));
+        let (rb, rc) = (RK::new(b), RK::new(c));
+        Some(match OpCode::from_u8(get_op(i))? {
+            OpCode::Move => Move { a, b },
+            OpCode::LoadK => LoadK { a, bx },
+            OpCode::LoadKX => LoadKX { a, ax: 0 },
+            OpCode::LoadBool => LoadBool { a, b, c },
+            OpCode::LoadNil => LoadNil { a, b },
+            OpCode::GetUpval => GetUpval { a, b },
+            OpCode::GetTabUp => GetTabUp { a, b, c: rc },
+            OpCode::GetTable => GetTable { a, b, c: rc },
+            OpCode::SetTabUp => SetTabUp { a, b: rb, c: rc },
+            OpCode::SetUpval => SetUpval { a, b },
+            OpCode::SetTable => SetTable { a, b: rb, c: rc },
+            OpCode::NewTable => NewTable { a, b, c },
+            OpCode::Self_ => Self_ { a, b, c: rc },
+            OpCode::Add => Add { a, b: rb, c: rc },
+            OpCode::Sub => Sub { a, b: rb, c: rc },
+            OpCode::Mul => Mul { a, b: rb, c: rc },
+            OpCode::Mod => Mod { a, b: rb, c: rc },
+            OpCode::Pow => Pow { a, b: rb, c: rc },
+            OpCode::Div => Div { a, b: rb, c: rc },
+            OpCode::IDiv => IDiv { a, b: rb, c: rc },
+            OpCode::BAnd => BAnd { a, b: rb, c: rc },
+            OpCode::BOr => BOr { a, b: rb, c: rc },
+            OpCode::BXor => BXor { a, b: rb, c: rc },
+            OpCode::Shl => Shl { a, b: rb, c: rc },
+            OpCode::Shr => Shr { a, b: rb, c: rc },
+            OpCode::Unm => Unm { a, b },
+            OpCode::BNot => BNot { a, b },
+            OpCode::Not => Not { a, b },
+            OpCode::Len => Len { a, b },
+            OpCode::Concat => Concat { a, b, c },
+            OpCode::Jmp => Jmp { a, sbx },
+            OpCode::Eq => Eq { a, b: rb, c: rc },
+            OpCode::Lt => Lt { a, b: rb, c: rc },
+            OpCode::Le => Le { a, b: rb, c: rc },
+            OpCode::Test => Test { a, c },
+            OpCode::TestSet => TestSet { a, b, c },
+            OpCode::Call => Call { a, b, c },
+            OpCode::TailCall => TailCall { a, b, c },
+            OpCode::Return => Return { a, b },
+            OpCode::ForLoop => ForLoop { a, sbx },
+            OpCode::ForPrep => ForPrep { a, sbx },
+            OpCode::TForCall => TForCall { a, c },
+            OpCode::TForLoop => TForLoop { a, sbx },
+            OpCode::SetList => SetList { a, b, c: c as _ },
+            OpCode::Closure => Closure { a, bx },
+            OpCode::VarArg => VarArg { a, b },
+            OpCode::ExtraArg => ExtraArg { ax: get_ax(i) },
+        })
+    }
+
+    pub fn opcode(&self) -> OpCode {
+        use Instruction::*;
+
+        match self {
+            Move { .. } => OpCode::Move,
+            LoadK { .. } => OpCode::LoadK,
+            LoadKX { .. } => OpCode::LoadKX,
+            LoadBool { .. } => OpCode::LoadBool,
+            LoadNil { .. } => OpCode::LoadNil,
+            GetUpval { .. } => OpCode::GetUpval,
+            GetTabUp { .. } => OpCode::GetTabUp,
+            GetTable { .. } => OpCode::GetTable,
+            SetTabUp { .. } => OpCode::SetTabUp,
+            SetUpval { .. } => OpCode::SetUpval,
+            SetTable { .. } => OpCode::SetTable,
+            NewTable { .. } => OpCode::NewTable,
+            Self_ { .. } => OpCode::Self_,
+            Add { .. } => OpCode::Add,
+            Sub { .. } => OpCode::Sub,
+            Mul { .. } => OpCode::Mul,
+            Mod { .. } => OpCode::Mod,
+            Pow { .. } => OpCode::Pow,
+            Div { .. } => OpCode::Div,
+            IDiv { .. } => OpCode::IDiv,
+            BAnd { .. } => OpCode::BAnd,
+            BOr { .. } => OpCode::BOr,
+            BXor { .. } => OpCode::BXor,
+            Shl { .. } => OpCode::Shl,
+            Shr { .. } => OpCode::Shr,
+            Unm { .. } => OpCode::Unm,
+            BNot { .. } => OpCode::BNot,
+            Not { .. } => OpCode::Not,
+            Len { .. } => OpCode::Len,
+            Concat { .. } => OpCode::Concat,
+            Jmp { .. } => OpCode::Jmp,
+            Eq { .. } => OpCode::Eq,
+            Lt { .. } => OpCode::Lt,
+            Le { .. } => OpCode::Le,
+            Test { .. } => OpCode::Test,
+            TestSet { .. } => OpCode::TestSet,
+            Call { .. } => OpCode::Call,
+            TailCall { .. } => OpCode::TailCall,
+            Return { .. } => OpCode::Return,
+            ForLoop { .. } => OpCode::ForLoop,
+            ForPrep { .. } => OpCode::ForPrep,
+            TForCall { .. } => OpCode::TForCall,
+            TForLoop { .. } => OpCode::TForLoop,
+            SetList { .. } => OpCode::SetList,
+            Closure { .. } => OpCode::Closure,
+            VarArg { .. } => OpCode::VarArg,
+            ExtraArg { .. } => OpCode::ExtraArg,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.opcode().name()
+    }
+}
+
+/// Decode all instructions of a chunk into `(pc, instruction)` pairs.
+///
+/// LOADKX and SETLIST with C == 0 are paired with their trailing EXTRAARG, which is consumed along with them
+pub fn decode(chunk: &LuaChunk) -> Result<Vec<(usize, Instruction)>, DecodeError> {
+    use super::lua52::extra_arg;
+
+    let code = &chunk.instructions;
+    let mut result = Vec::with_capacity(code.len());
+    let mut pc = 0;
+    while pc < code.len() {
+        let raw = code[pc];
+        let mut insn = Instruction::decode(raw).ok_or(DecodeError::InvalidOpcode { pc, raw })?;
+        let size = match &mut insn {
+            Instruction::LoadKX { ax, .. } => {
+                *ax = extra_arg(code, pc + 1, OpCode::ExtraArg as _)?;
+                2
+            }
+            Instruction::SetList { c, .. } if *c == 0 => {
+                *c = extra_arg(code, pc + 1, OpCode::ExtraArg as _)?;
+                2
+            }
+            _ => 1,
+        };
+        result.push((pc, insn));
+        pc += size;
+    }
+    Ok(result)
+}
@@ -1,3 +1,4 @@
+use super::lua53::invalid_constant;
 use super::*;
 use complete::le_u8;
 
@@ -58,7 +59,7 @@ pub fn lua_local<'a>(_header: &LuaHeader) -> impl Parser<&'a [u8], LuaLocal, Err
 pub fn lua_chunk<'h, 'a: 'h>(
     header: &'h LuaHeader,
 ) -> impl Parser<&'a [u8], LuaChunk, ErrorTree<&'a [u8]>> + 'h {
-    |input| {
+    limited(move |input| {
         let (input, (name, line_defined, last_line_defined, num_params, is_vararg, max_stack)) =
             context(
                 "chunk header",
@@ -82,12 +83,13 @@ pub fn lua_chunk<'h, 'a: 'h>(
                         take_lv_float,
                         take_lv_str,
                         take_lv_u64,
+                        invalid_constant,
                     )),
                 )
                 .context("count constants"),
                 length_count(lua_int.map(|x| x as usize), load_upvalue).context("count upvalues"),
                 |i| {
-                    length_count(lua_int.map(|x| x as usize), lua_chunk(header))
+                    length_protos(lua_int.map(|x| x as usize), lua_chunk(header))
                         .context("count prototypes")
                         .parse(i)
                 },
@@ -107,7 +109,7 @@ pub fn lua_chunk<'h, 'a: 'h>(
                 constants,
                 upvalues,
                 prototypes,
-                _line_info,
+                line_info,
                 source_lines,
                 locals,
                 upvalue_names,
@@ -118,6 +120,7 @@ pub fn lua_chunk<'h, 'a: 'h>(
                     last_line_defined,
                     num_upvalues: upvalues.len() as _,
                     flags: 0,
+                    type_info: None,
                     num_params,
                     is_vararg: if is_vararg != 0 {
                         Some(LuaVarArgInfo::new())
@@ -133,12 +136,13 @@ pub fn lua_chunk<'h, 'a: 'h>(
                     upvalue_names,
                     num_constants: vec![],
                     upvalue_infos: upvalues,
+                    line_info: line_info.into_iter().map(|d| d as i8).collect(),
                 }
             },
         )
         .context("chunk")
         .parse(input)
-    }
+    })
 }
 
 fn take_lv_nil(input: &[u8]) -> IResult<&[u8], LuaConstant> {
@@ -170,3 +174,898 @@ fn take_lv_u64(input: &[u8]) -> IResult<&[u8], LuaConstant> {
     let (input, (_, val)) = tuple((tag(b"\x03"), complete::le_u64))(input)?;
     Ok((input, LuaConstant::Number(LuaNumber::Integer(val as _))))
 }
+
+pub use super::lua51::RK;
+
+/// Inverse of `load_size`, the big-endian groups of 7 bits with the last byte marked
+pub fn dump_size(d: &mut DumpState, mut x: u64) {
+    let mut buf = vec![];
+    loop {
+        buf.push((x & 0x7f) as u8);
+        x >>= 7;
+        if x == 0 {
+            break;
+        }
+    }
+    buf[0] |= 0x80;
+    d.out.extend(buf.iter().rev());
+}
+
+/// Inverse of `lua_int`
+pub fn dump_int(d: &mut DumpState, x: u64) -> Result<(), DumpError> {
+    if x > i32::MAX as u64 {
+        return Err(DumpError::Overflow { value: x });
+    }
+    dump_size(d, x);
+    Ok(())
+}
+
+/// Inverse of `load_string`
+pub fn dump_string(d: &mut DumpState, s: &[u8]) {
+    dump_size(d, s.len() as u64 + 1);
+    d.bytes(s);
+}
+
+/// Source name of a chunk, see [`lua51::dump_source`]
+pub fn dump_source(d: &mut DumpState, s: &[u8]) {
+    if s.is_empty() {
+        dump_size(d, 0);
+    } else {
+        dump_string(d, s);
+    }
+}
+
+/// Inverse of `lua_chunk`
+pub fn dump_chunk(d: &mut DumpState, f: &LuaChunk) -> Result<(), DumpError> {
+    let count = |d: &mut DumpState, n: usize| dump_int(d, n as _);
+
+    dump_source(d, &f.name);
+    dump_int(d, f.line_defined)?;
+    dump_int(d, f.last_line_defined)?;
+    d.bytes(&[f.num_params, f.is_vararg.is_some() as u8, f.max_stack]);
+    count(d, f.instructions.len())?;
+    for &i in &f.instructions {
+        d.instruction(i)?;
+    }
+    count(d, f.constants.len())?;
+    for k in &f.constants {
+        match k {
+            LuaConstant::Null => d.byte(0),
+            LuaConstant::Bool(false) => d.byte(1),
+            LuaConstant::Bool(true) => d.byte(0x11),
+            LuaConstant::Number(n @ LuaNumber::Float(_)) => {
+                d.byte(0x13);
+                d.number(n)?;
+            }
+            LuaConstant::Number(LuaNumber::Integer(i)) => {
+                d.byte(3);
+                d.uint(*i as u64, 8)?;
+            }
+            LuaConstant::String(s) => {
+                d.byte(if s.len() <= lua53::MAX_SHORT_LEN {
+                    4
+                } else {
+                    0x14
+                });
+                dump_string(d, s);
+            }
+            _ => return Err(DumpError::UnsupportedConstant),
+        }
+    }
+    count(d, f.upvalue_infos.len())?;
+    for u in &f.upvalue_infos {
+        d.bytes(&[u.on_stack as u8, u.id, u.kind]);
+    }
+    count(d, f.prototypes.len())?;
+    for p in &f.prototypes {
+        dump_chunk(d, p)?;
+    }
+    count(d, f.line_info.len())?;
+    d.out.extend(f.line_info.iter().map(|&l| l as u8));
+    count(d, f.source_lines.len())?;
+    for &(pc, line) in &f.source_lines {
+        dump_int(d, pc as _)?;
+        dump_int(d, line as _)?;
+    }
+    count(d, f.locals.len())?;
+    for l in &f.locals {
+        dump_string(d, l.name.as_bytes());
+        dump_int(d, l.start_pc)?;
+        dump_int(d, l.end_pc)?;
+    }
+    count(d, f.upvalue_names.len())?;
+    for name in &f.upvalue_names {
+        dump_string(d, name);
+    }
+    Ok(())
+}
+
+pub const SIZE_OP: u32 = 7;
+pub const SIZE_A: u32 = 8;
+pub const SIZE_B: u32 = 8;
+pub const SIZE_C: u32 = 8;
+pub const SIZE_BX: u32 = SIZE_C + SIZE_B + 1;
+pub const SIZE_AX: u32 = SIZE_BX + SIZE_A;
+pub const SIZE_SJ: u32 = SIZE_BX + SIZE_A;
+
+pub const POS_OP: u32 = 0;
+pub const POS_A: u32 = POS_OP + SIZE_OP;
+pub const POS_K: u32 = POS_A + SIZE_A;
+pub const POS_B: u32 = POS_K + 1;
+pub const POS_C: u32 = POS_B + SIZE_B;
+pub const POS_BX: u32 = POS_K;
+pub const POS_AX: u32 = POS_A;
+pub const POS_SJ: u32 = POS_A;
+
+pub const MAXARG_BX: u32 = (1 << SIZE_BX) - 1;
+pub const MAXARG_AX: u32 = (1 << SIZE_AX) - 1;
+pub const MAXARG_SJ: u32 = (1 << SIZE_SJ) - 1;
+pub const MAXARG_C: u32 = (1 << SIZE_C) - 1;
+
+pub const OFFSET_SBX: i32 = (MAXARG_BX >> 1) as i32;
+pub const OFFSET_SJ: i32 = (MAXARG_SJ >> 1) as i32;
+pub const OFFSET_SC: i32 = (MAXARG_C >> 1) as i32;
+
+#[inline]
+pub fn get_op(i: u32) -> u8 {
+    ((i >> POS_OP) & ((1 << SIZE_OP) - 1)) as u8
+}
+
+#[inline]
+pub fn get_a(i: u32) -> u8 {
+    ((i >> POS_A) & ((1 << SIZE_A) - 1)) as u8
+}
+
+#[inline]
+pub fn get_b(i: u32) -> u8 {
+    ((i >> POS_B) & ((1 << SIZE_B) - 1)) as u8
+}
+
+#[inline]
+pub fn get_c(i: u32) -> u8 {
+    ((i >> POS_C) & ((1 << SIZE_C) - 1)) as u8
+}
+
+#[inline]
+pub fn get_k(i: u32) -> bool {
+    (i >> POS_K) & 1 != 0
+}
+
+#[inline]
+pub fn get_sb(i: u32) -> i32 {
+    get_b(i) as i32 - OFFSET_SC
+}
+
+#[inline]
+pub fn get_sc(i: u32) -> i32 {
+    get_c(i) as i32 - OFFSET_SC
+}
+
+#[inline]
+pub fn get_bx(i: u32) -> u32 {
+    (i >> POS_BX) & MAXARG_BX
+}
+
+#[inline]
+pub fn get_sbx(i: u32) -> i32 {
+    get_bx(i) as i32 - OFFSET_SBX
+}
+
+#[inline]
+pub fn get_ax(i: u32) -> u32 {
+    (i >> POS_AX) & MAXARG_AX
+}
+
+#[inline]
+pub fn get_sj(i: u32) -> i32 {
+    ((i >> POS_SJ) & MAXARG_SJ) as i32 - OFFSET_SJ
+}
+
+/// Names of the metamethod events (TMS in ltm.h), indexed by the C operand of MMBIN/MMBINI/MMBINK
+pub const TM_NAMES: [&str; 25] = [
+    "__index",
+    "__newindex",
+    "__gc",
+    "__mode",
+    "__len",
+    "__eq",
+    "__add",
+    "__sub",
+    "__mul",
+    "__mod",
+    "__pow",
+    "__div",
+    "__idiv",
+    "__band",
+    "__bor",
+    "__bxor",
+    "__shl",
+    "__shr",
+    "__unm",
+    "__bnot",
+    "__lt",
+    "__le",
+    "__concat",
+    "__call",
+    "__close",
+];
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpCode {
+    Move,
+    LoadI,
+    LoadF,
+    LoadK,
+    LoadKX,
+    LoadFalse,
+    LFalseSkip,
+    LoadTrue,
+    LoadNil,
+    GetUpval,
+    SetUpval,
+    GetTabUp,
+    GetTable,
+    GetI,
+    GetField,
+    SetTabUp,
+    SetTable,
+    SetI,
+    SetField,
+    NewTable,
+    Self_,
+    AddI,
+    AddK,
+    SubK,
+    MulK,
+    ModK,
+    PowK,
+    DivK,
+    IDivK,
+    BAndK,
+    BOrK,
+    BXorK,
+    ShrI,
+    ShlI,
+    Add,
+    Sub,
+    Mul,
+    Mod,
+    Pow,
+    Div,
+    IDiv,
+    BAnd,
+    BOr,
+    BXor,
+    Shl,
+    Shr,
+    MmBin,
+    MmBinI,
+    MmBinK,
+    Unm,
+    BNot,
+    Not,
+    Len,
+    Concat,
+    Close,
+    Tbc,
+    Jmp,
+    Eq,
+    Lt,
+    Le,
+    EqK,
+    EqI,
+    LtI,
+    LeI,
+    GtI,
+    GeI,
+    Test,
+    TestSet,
+    Call,
+    TailCall,
+    Return,
+    Return0,
+    Return1,
+    ForLoop,
+    ForPrep,
+    TForPrep,
+    TForCall,
+    TForLoop,
+    SetList,
+    Closure,
+    VarArg,
+    VarArgPrep,
+    ExtraArg,
+}
+
+impl OpCode {
+    pub const NAMES: [&'static str; 83] = [
+        "MOVE",
+        "LOADI",
+        "LOADF",
+        "LOADK",
+        "LOADKX",
+        "LOADFALSE",
+        "LFALSESKIP",
+        "LOADTRUE",
+        "LOADNIL",
+        "GETUPVAL",
+        "SETUPVAL",
+        "GETTABUP",
+        "GETTABLE",
+        "GETI",
+        "GETFIELD",
+        "SETTABUP",
+        "SETTABLE",
+        "SETI",
+        "SETFIELD",
+        "NEWTABLE",
+        "SELF",
+        "ADDI",
+        "ADDK",
+        "SUBK",
+        "MULK",
+        "MODK",
+        "POWK",
+        "DIVK",
+        "IDIVK",
+        "BANDK",
+        "BORK",
+        "BXORK",
+        "SHRI",
+        "SHLI",
+        "ADD",
+        "SUB",
+        "MUL",
+        "MOD",
+        "POW",
+        "DIV",
+        "IDIV",
+        "BAND",
+        "BOR",
+        "BXOR",
+        "SHL",
+        "SHR",
+        "MMBIN",
+        "MMBINI",
+        "MMBINK",
+        "UNM",
+        "BNOT",
+        "NOT",
+        "LEN",
+        "CONCAT",
+        "CLOSE",
+        "TBC",
+        "JMP",
+        "EQ",
+        "LT",
+        "LE",
+        "EQK",
+        "EQI",
+        "LTI",
+        "LEI",
+        "GTI",
+        "GEI",
+        "TEST",
+        "TESTSET",
+        "CALL",
+        "TAILCALL",
+        "RETURN",
+        "RETURN0",
+        "RETURN1",
+        "FORLOOP",
+        "FORPREP",
+        "TFORPREP",
+        "TFORCALL",
+        "TFORLOOP",
+        "SETLIST",
+        "CLOSURE",
+        "VARARG",
+        "VARARGPREP",
+        "EXTRAARG",
+    ];
+
+    pub fn from_u8(op: u8) -> Option<Self> {
+        if (op as usize) < Self::NAMES.len() {
+            Some(unsafe { core::mem::transmute::<u8, Self>(op) })
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+}
+
+/// Decoded lua54 instruction, the operand names follow lopcodes.h,
+/// signed operands (sBx, sJ, sB, sC) are already offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// R[A] := R[B]
+    Move { a: u8, b: u8 },
+    /// R[A] := sBx
+    LoadI { a: u8, sbx: i32 },
+    /// R[A] := (lua_Number)sBx
+    LoadF { a: u8, sbx: i32 },
+    /// R[A] := K[Bx]
+    LoadK { a: u8, bx: u32 },
+    /// R[A] := K[extra arg]
+    LoadKX { a: u8, ax: u32 },
+    /// R[A] := false
+    LoadFalse { a: u8 },
+    /// R[A] := false; pc++
+    LFalseSkip { a: u8 },
+    /// R[A] := true
+    LoadTrue { a: u8 },
+    /// R[A], R[A+1], ..., R[A+B] := nil
+    LoadNil { a: u8, b: u8 },
+    /// R[A] := UpValue[B]
+    GetUpval { a: u8, b: u8 },
+    /// UpValue[B] := R[A]
+    SetUpval { a: u8, b: u8 },
+    /// R[A] := UpValue[B][K[C]:shortstring]
+    GetTabUp { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B][R[C]]
+    GetTable { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B][C]
+    GetI { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B][K[C]:shortstring]
+    GetField { a: u8, b: u8, c: u8 },
+    /// UpValue[A][K[B]:shortstring] := RK(C)
+    SetTabUp { a: u8, b: u8, c: RK },
+    /// R[A][R[B]] := RK(C)
+    SetTable { a: u8, b: u8, c: RK },
+    /// R[A][B] := RK(C)
+    SetI { a: u8, b: u8, c: RK },
+    /// R[A][K[B]:shortstring] := RK(C)
+    SetField { a: u8, b: u8, c: RK },
+    /// R[A] := {}, C is the array size including the trailing EXTRAARG
+    NewTable { a: u8, b: u8, c: u32, k: bool },
+    /// R[A+1] := R[B]; R[A] := R[B][RK(C):string]
+    Self_ { a: u8, b: u8, c: RK },
+    /// R[A] := R[B] + sC
+    AddI { a: u8, b: u8, sc: i32 },
+    /// R[A] := R[B] + K[C]:number
+    AddK { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] - K[C]:number
+    SubK { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] * K[C]:number
+    MulK { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] % K[C]:number
+    ModK { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] ^ K[C]:number
+    PowK { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] / K[C]:number
+    DivK { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] // K[C]:number
+    IDivK { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] & K[C]:integer
+    BAndK { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] | K[C]:integer
+    BOrK { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] ~ K[C]:integer
+    BXorK { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] >> sC
+    ShrI { a: u8, b: u8, sc: i32 },
+    /// R[A] := sC << R[B]
+    ShlI { a: u8, b: u8, sc: i32 },
+    /// R[A] := R[B] + R[C]
+    Add { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] - R[C]
+    Sub { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] * R[C]
+    Mul { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] % R[C]
+    Mod { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] ^ R[C]
+    Pow { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] / R[C]
+    Div { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] // R[C]
+    IDiv { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] & R[C]
+    BAnd { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] | R[C]
+    BOr { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] ~ R[C]
+    BXor { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] << R[C]
+    Shl { a: u8, b: u8, c: u8 },
+    /// R[A] := R[B] >> R[C]
+    Shr { a: u8, b: u8, c: u8 },
+    /// call C metamethod over R[A] and R[B]
+    MmBin { a: u8, b: u8, c: u8 },
+    /// call C metamethod over R[A] and sB
+    MmBinI { a: u8, sb: i32, c: u8, k: bool },
+    /// call C metamethod over R[A] and K[B]
+    MmBinK { a: u8, b: u8, c: u8, k: bool },
+    /// R[A] := -R[B]
+    Unm { a: u8, b: u8 },
+    /// R[A] := ~R[B]
+    BNot { a: u8, b: u8 },
+    /// R[A] := not R[B]
+    Not { a: u8, b: u8 },
+    /// R[A] := #R[B] (length operator)
+    Len { a: u8, b: u8 },
+    /// R[A] := R[A].. ... ..R[A + B - 1]
+    Concat { a: u8, b: u8 },
+    /// close all upvalues >= R[A]
+    Close { a: u8 },
+    /// mark variable A "to be closed"
+    Tbc { a: u8 },
+    /// pc += sJ
+    Jmp { sj: i32 },
+    /// if ((R[A] == R[B]) ~= k) then pc++
+    Eq { a: u8, b: u8, k: bool },
+    /// if ((R[A] <  R[B]) ~= k) then pc++
+    Lt { a: u8, b: u8, k: bool },
+    /// if ((R[A] <= R[B]) ~= k) then pc++
+    Le { a: u8, b: u8, k: bool },
+    /// if ((R[A] == K[B]) ~= k) then pc++
+    EqK { a: u8, b: u8, k: bool },
+    /// if ((R[A] == sB) ~= k) then pc++
+    EqI { a: u8, sb: i32, k: bool },
+    /// if ((R[A] < sB) ~= k) then pc++
+    LtI { a: u8, sb: i32, k: bool },
+    /// if ((R[A] <= sB) ~= k) then pc++
+    LeI { a: u8, sb: i32, k: bool },
+    /// if ((R[A] > sB) ~= k) then pc++
+    GtI { a: u8, sb: i32, k: bool },
+    /// if ((R[A] >= sB) ~= k) then pc++
+    GeI { a: u8, sb: i32, k: bool },
+    /// if (not R[A] == k) then pc++
+    Test { a: u8, k: bool },
+    /// if (not R[B] == k) then pc++ else R[A] := R[B]
+    TestSet { a: u8, b: u8, k: bool },
+    /// R[A], ... ,R[A+C-2] := R[A](R[A+1], ... ,R[A+B-1])
+    Call { a: u8, b: u8, c: u8 },
+    /// return R[A](R[A+1], ... ,R[A+B-1])
+    TailCall { a: u8, b: u8, c: u8, k: bool },
+    /// return R[A], ... ,R[A+B-2]
+    Return { a: u8, b: u8, c: u8, k: bool },
+    /// return
+    Return0,
+    /// return R[A]
+    Return1 { a: u8 },
+    /// update counters; if loop continues then pc-=Bx;
+    ForLoop { a: u8, bx: u32 },
+    /// <check values and prepare counters>; if not to run then pc+=Bx+1;
+    ForPrep { a: u8, bx: u32 },
+    /// create upvalue for R[A + 3]; pc+=Bx
+    TForPrep { a: u8, bx: u32 },
+    /// R[A+4], ... ,R[A+3+C] := R[A](R[A+1], R[A+2]);
+    TForCall { a: u8, c: u8 },
+    /// if R[A+2] ~= nil then { R[A]=R[A+2]; pc -= Bx }
+    TForLoop { a: u8, bx: u32 },
+    /// R[A][C+i] := R[A+i], 1 <= i <= B, C includes the trailing EXTRAARG when k is set
+    SetList { a: u8, b: u8, c: u32, k: bool },
+    /// R[A] := closure(KPROTO[Bx])
+    Closure { a: u8, bx: u32 },
+    /// R[A], R[A+1], ..., R[A+C-2] = vararg
+    VarArg { a: u8, c: u8 },
+    /// (adjust vararg parameters)
+    VarArgPrep { a: u8 },
+    /// extra (larger) argument for previous opcode
+    ExtraArg { ax: u32 },
+}
+
+impl Instruction {
+    /// Decode a single word, LOADKX, NEWTABLE and SETLIST with k need the following
+    /// EXTRAARG which is paired by [`decode`]
+    pub fn decode(i: u32) -> Option<Self> {
+        use Instruction::*;
+
+        let (a, b, c, k) = (get_a(i), get_b(i), get_c(i), get_k(i));
+        let (bx, sbx, sb, sc) = (get_bx(i), get_sbx(i), get_sb(i), get_sc(i));
+        let rkc = if k {
+            RK::Constant(c as _)
+        } else {
+            RK::Register(c as _)
+        };
+        Some(match OpCode::from_u8(get_op(i))? {
+            OpCode::Move => Move { a, b },
+            OpCode::LoadI => LoadI { a, sbx },
+            OpCode::LoadF => LoadF { a, sbx },
+            OpCode::LoadK => LoadK { a, bx },
+            OpCode::LoadKX => LoadKX { a, ax: 0 },
+            OpCode::LoadFalse => LoadFalse { a },
+            OpCode::LFalseSkip => LFalseSkip { a },
+            OpCode::LoadTrue => LoadTrue { a },
+            OpCode::LoadNil => LoadNil { a, b },
+            OpCode::GetUpval => GetUpval { a, b },
+            OpCode::SetUpval => SetUpval { a, b },
+            OpCode::GetTabUp => GetTabUp { a, b, c },
+            OpCode::GetTable => GetTable { a, b, c },
+            OpCode::GetI => GetI { a, b, c },
+            OpCode::GetField => GetField { a, b, c },
+            OpCode::SetTabUp => SetTabUp { a, b, c: rkc },
+            OpCode::SetTable => SetTable { a, b, c: rkc },
+            OpCode::SetI => SetI { a, b, c: rkc },
+            OpCode::SetField => SetField { a, b, c: rkc },
+            OpCode::NewTable => NewTable { a, b, c: c as _, k },
+            OpCode::Self_ => Self_ { a, b, c: rkc },
+            OpCode::AddI => AddI { a, b, sc },
+            OpCode::AddK => AddK { a, b, c },
+            OpCode::SubK => SubK { a, b, c },
+            OpCode::MulK => MulK { a, b, c },
+            OpCode::ModK => ModK { a, b, c },
+            OpCode::PowK => PowK { a, b, c },
+            OpCode::DivK => DivK { a, b, c },
+            OpCode::IDivK => IDivK { a, b, c },
+            OpCode::BAndK => BAndK { a, b, c },
+            OpCode::BOrK => BOrK { a, b, c },
+            OpCode::BXorK => BXorK { a, b, c },
+            OpCode::ShrI => ShrI { a, b, sc },
+            OpCode::ShlI => ShlI { a, b, sc },
+            OpCode::Add => Add { a, b, c },
+            OpCode::Sub => Sub { a, b, c },
+            OpCode::Mul => Mul { a, b, c },
+            OpCode::Mod => Mod { a, b, c },
+            OpCode::Pow => Pow { a, b, c },
+            OpCode::Div => Div { a, b, c },
+            OpCode::IDiv => IDiv { a, b, c },
+            OpCode::BAnd => BAnd { a, b, c },
+            OpCode::BOr => BOr { a, b, c },
+            OpCode::BXor => BXor { a, b, c },
+            OpCode::Shl => Shl { a, b, c },
+            OpCode::Shr => Shr { a, b, c },
+            OpCode::MmBin => MmBin { a, b, c },
+            OpCode::MmBinI => MmBinI { a, sb, c, k },
+            OpCode::MmBinK => MmBinK { a, b, c, k },
+            OpCode::Unm => Unm { a, b },
+            OpCode::BNot => BNot { a, b },
+            OpCode::Not => Not { a, b },
+            OpCode::Len => Len { a, b },
+            OpCode::Concat => Concat { a, b },
+            OpCode::Close => Close { a },
+            OpCode::Tbc => Tbc { a },
+            OpCode::Jmp => Jmp { sj: get_sj(i) },
+            OpCode::Eq => Eq { a, b, k },
+            OpCode::Lt => Lt { a, b, k },
+            OpCode::Le => Le { a, b, k },
+            OpCode::EqK => EqK { a, b, k },
+            OpCode::EqI => EqI { a, sb, k },
+            OpCode::LtI => LtI { a, sb, k },
+            OpCode::LeI => LeI { a, sb, k },
+            OpCode::GtI => GtI { a, sb, k },
+            OpCode::GeI => GeI { a, sb, k },
+            OpCode::Test => Test { a, k },
+            OpCode::TestSet => TestSet { a, b, k },
+            OpCode::Call => Call { a, b, c },
+            OpCode::TailCall => TailCall { a, b, c, k },
+            OpCode::Return => Return { a, b, c, k },
+            OpCode::Return0 => Return0,
+            OpCode::Return1 => Return1 { a },
+            OpCode::ForLoop => ForLoop { a, bx },
+            OpCode::ForPrep => ForPrep { a, bx },
+            OpCode::TForPrep => TForPrep { a, bx },
+            OpCode::TForCall => TForCall { a, c },
+            OpCode::TForLoop => TForLoop { a, bx },
+            OpCode::SetList => SetList { a, b, c: c as _, k },
+            OpCode::Closure => Closure { a, bx },
+            OpCode::VarArg => VarArg { a, c },
+            OpCode::VarArgPrep => VarArgPrep { a },
+            OpCode::ExtraArg => ExtraArg { ax: get_ax(i) },
+        })
+    }
+
+    pub fn opcode(&self) -> OpCode {
+        use Instruction::*;
+
+        match self {
+            Move { .. } => OpCode::Move,
+            LoadI { .. } => OpCode::LoadI,
+            LoadF { .. } => OpCode::LoadF,
+            LoadK { .. } => OpCode::LoadK,
+            LoadKX { .. } => OpCode::LoadKX,
+            LoadFalse { .. } => OpCode::LoadFalse,
+            LFalseSkip { .. } => OpCode::LFalseSkip,
+            LoadTrue { .. } => OpCode::LoadTrue,
+            LoadNil { .. } => OpCode::LoadNil,
+            GetUpval { .. } => OpCode::GetUpval,
+            SetUpval { .. } => OpCode::SetUpval,
+            GetTabUp { .. } => OpCode::GetTabUp,
+            GetTable { .. } => OpCode::GetTable,
+            GetI { .. } => OpCode::GetI,
+            GetField { .. } => OpCode::GetField,
+            SetTabUp { .. } => OpCode::SetTabUp,
+            SetTable { .. } => OpCode::SetTable,
+            SetI { .. } => OpCode::SetI,
+            SetField { .. } => OpCode::SetField,
+            NewTable { .. } => OpCode::NewTable,
+            Self_ { .. } => OpCode::Self_,
+            AddI { .. } => OpCode::AddI,
+            AddK { .. } => OpCode::AddK,
+            SubK { .. } => OpCode::SubK,
+            MulK { .. } => OpCode::MulK,
+            ModK { .. } => OpCode::ModK,
+            PowK { .. } => OpCode::PowK,
+            DivK { .. } => OpCode::DivK,
+            IDivK { .. } => OpCode::IDivK,
+            BAndK { .. } => OpCode::BAndK,
+            BOrK { .. } => OpCode::BOrK,
+            BXorK { .. } => OpCode::BXorK,
+            ShrI { .. } => OpCode::ShrI,
+            ShlI { .. } => OpCode::ShlI,
+            Add { .. } => OpCode::Add,
+            Sub { .. } => OpCode::Sub,
+            Mul { .. } => OpCode::Mul,
+            Mod { .. } => OpCode::Mod,
+            Pow { .. } => OpCode::Pow,
+            Div { .. } => OpCode::Div,
+            IDiv { .. } => OpCode::IDiv,
+            BAnd { .. } => OpCode::BAnd,
+            BOr { .. } => OpCode::BOr,
+            BXor { .. } => OpCode::BXor,
+            Shl { .. } => OpCode::Shl,
+            Shr { .. } => OpCode::Shr,
+            MmBin { .. } => OpCode::MmBin,
+            MmBinI { .. } => OpCode::MmBinI,
+            MmBinK { .. } => OpCode::MmBinK,
+            Unm { .. } => OpCode::Unm,
+            BNot { .. } => OpCode::BNot,
+            Not { .. } => OpCode::Not,
+            Len { .. } => OpCode::Len,
+            Concat { .. } => OpCode::Concat,
+            Close { .. } => OpCode::Close,
+            Tbc { .. } => OpCode::Tbc,
+            Jmp { .. } => OpCode::Jmp,
+            Eq { .. } => OpCode::Eq,
+            Lt { .. } => OpCode::Lt,
+            Le { .. } => OpCode::Le,
+            EqK { .. } => OpCode::EqK,
+            EqI { .. } => OpCode::EqI,
+            LtI { .. } => OpCode::LtI,
+            LeI { .. } => OpCode::LeI,
+            GtI { .. } => OpCode::GtI,
+            GeI { .. } => OpCode::GeI,
+            Test { .. } => OpCode::Test,
+            TestSet { .. } => OpCode::TestSet,
+            Call { .. } => OpCode::Call,
+            TailCall { .. } => OpCode::TailCall,
+            Return { .. } => OpCode::Return,
+            Return0 => OpCode::Return0,
+            Return1 { .. } => OpCode::Return1,
+            ForLoop { .. } => OpCode::ForLoop,
+            ForPrep { .. } => OpCode::ForPrep,
+            TForPrep { .. } => OpCode::TForPrep,
+            TForCall { .. } => OpCode::TForCall,
+            TForLoop { .. } => OpCode::TForLoop,
+            SetList { .. } => OpCode::SetList,
+            Closure { .. } => OpCode::Closure,
+            VarArg { .. } => OpCode::VarArg,
+            VarArgPrep { .. } => OpCode::VarArgPrep,
+            ExtraArg { .. } => OpCode::ExtraArg,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.opcode().name()
+    }
+
+    /// The explicit jump target of the instruction located at `pc`
+    pub fn jump_target(&self, pc: usize) -> Option<usize> {
+        let pc = pc as i64 + 1;
+        let target = match *self {
+            Self::Jmp { sj } => pc + sj as i64,
+            Self::ForLoop { bx, .. } | Self::TForLoop { bx, .. } => pc - bx as i64,
+            Self::ForPrep { bx, .. } => pc + bx as i64 + 1,
+            Self::TForPrep { bx, .. } => pc + bx as i64,
+            _ => return None,
+        };
+        usize::try_from(target).ok()
+    }
+
+    /// Whether the instruction skips the next one (always a JMP for comparisons) on some condition
+    pub fn is_test(&self) -> bool {
+        matches!(
+            self,
+            Self::Eq { .. }
+                | Self::Lt { .. }
+                | Self::Le { .. }
+                | Self::EqK { .. }
+                | Self::EqI { .. }
+                | Self::LtI { .. }
+                | Self::LeI { .. }
+                | Self::GtI { .. }
+                | Self::GeI { .. }
+                | Self::Test { .. }
+                | Self::TestSet { .. }
+        )
+    }
+
+    /// The MMBIN family opcode which must follow an arithmetic instruction
+    pub fn mmbin_opcode(&self) -> Option<OpCode> {
+        use OpCode::*;
+
+        match self.opcode() {
+            Add | Sub | Mul | Mod | Pow | Div | IDiv | BAnd | BOr | BXor | Shl | Shr => Some(MmBin),
+            AddI | ShrI | ShlI => Some(MmBinI),
+            AddK | SubK | MulK | ModK | PowK | DivK | IDivK | BAndK | BOrK | BXorK => Some(MmBinK),
+            _ => None,
+        }
+    }
+
+    /// The metamethod name called by a MMBIN family instruction
+    pub fn metamethod(&self) -> Option<&'static str> {
+        match *self {
+            Self::MmBin { c, .. } | Self::MmBinI { c, .. } | Self::MmBinK { c, .. } => {
+                TM_NAMES.get(c as usize).copied()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Find the MMBIN family instruction paired with the arithmetic instruction at `index` of the
+/// result of [`decode`], the arithmetic one skips it when it doesn't fall back to the metamethod
+pub fn paired_mmbin(code: &[(usize, Instruction)], index: usize) -> Option<&(usize, Instruction)> {
+    let op = code.get(index)?.1.mmbin_opcode()?;
+    code.get(index + 1).filter(|(_, i)| i.opcode() == op)
+}
+
+/// Decode all instructions of a chunk into `(pc, instruction)` pairs.
+///
+/// LOADKX, NEWTABLE and SETLIST with k are paired with their trailing EXTRAARG, which is consumed along with them
+pub fn decode(chunk: &LuaChunk) -> Result<Vec<(usize, Instruction)>, DecodeError> {
+    let code = &chunk.instructions;
+    let extra_arg = |pc: usize| -> Result<u32, DecodeError> {
+        let raw = *code.get(pc).ok_or(DecodeError::Truncated { pc: pc - 1 })?;
+        if get_op(raw) != OpCode::ExtraArg as u8 {
+            return Err(DecodeError::InvalidOpcode { pc, raw });
+        }
+        Ok(get_ax(raw))
+    };
+
+    let mut result = Vec::with_capacity(code.len());
+    let mut pc = 0;
+    while pc < code.len() {
+        let raw = code[pc];
+        let mut insn = Instruction::decode(raw).ok_or(DecodeError::InvalidOpcode { pc, raw })?;
+        let size = match &mut insn {
+            Instruction::LoadKX { ax, .. } => {
+                *ax = extra_arg(pc + 1)?;
+                2
+            }
+            Instruction::NewTable { c, k, .. } => {
+                let ax = extra_arg(pc + 1)?;
+                if *k {
+                    *c |= ax << SIZE_C;
+                }
+                2
+            }
+            Instruction::SetList { c, k: true, .. } => {
+                *c |= extra_arg(pc + 1)? << SIZE_C;
+                2
+            }
+            _ => 1,
+        };
+        result.push((pc, insn));
+        pc += size;
+    }
+    Ok(result)
+}
+
+/// Marker in `LuaChunk::line_info` of an instruction whose line is in the absolute line info
+pub const ABSLINEINFO: i8 = -0x80;
+
+/// Source line of the instruction at `pc`, as `luaG_getfuncline` of ldebug.c,
+/// `None` if the chunk is stripped
+pub fn source_line(chunk: &LuaChunk, pc: usize) -> Option<i64> {
+    if chunk.line_info.len() <= pc {
+        return None;
+    }
+    let (basepc, mut line) = chunk
+        .source_lines
+        .iter()
+        .take_while(|&&(abspc, _)| abspc as usize <= pc)
+        .last()
+        .map_or((0, chunk.line_defined as i64), |&(abspc, line)| {
+            (abspc as usize + 1, line as i64)
+        });
+    for &delta in &chunk.line_info[basepc..=pc] {
+        line += delta as i64;
+    }
+    Some(line)
+}
@@ -2,7 +2,7 @@
 
 use std::cell::RefCell;
 
-use nom::{multi::count, number::complete::le_u8};
+use nom::{number::complete::le_u8, sequence::terminated};
 use nom_leb128::{leb128_u32, leb128_u64, leb128_usize};
 
 use super::*;
@@ -38,6 +38,26 @@ pub const BCDUMP_KTAB_INT: usize = 3;
 pub const BCDUMP_KTAB_NUM: usize = 4;
 pub const BCDUMP_KTAB_STR: usize = 5;
 
+/* Builtin variable names of the debug info, coded as a single byte. */
+pub const VARNAME_END: u8 = 0;
+pub const VARNAME_FOR_IDX: u8 = 1;
+pub const VARNAME_FOR_STOP: u8 = 2;
+pub const VARNAME_FOR_STEP: u8 = 3;
+pub const VARNAME_FOR_GEN: u8 = 4;
+pub const VARNAME_FOR_STATE: u8 = 5;
+pub const VARNAME_FOR_CTL: u8 = 6;
+pub const VARNAME__MAX: u8 = 7;
+
+pub const VARNAMES: [&str; VARNAME__MAX as usize] = [
+    "",
+    "(for index)",
+    "(for limit)",
+    "(for step)",
+    "(for generator)",
+    "(for state)",
+    "(for control)",
+];
+
 pub fn uleb128_33(mut input: &[u8]) -> IResult<&[u8], u32, ErrorTree<&[u8]>> {
     let v;
     (input, v) = le_u8(input)?;
@@ -47,6 +67,9 @@ pub fn uleb128_33(mut input: &[u8]) -> IResult<&[u8], u32, ErrorTree<&[u8]>> {
         v &= 0x3f;
         loop {
             sh += 7;
+            if sh >= 32 {
+                return fail_expected(input, "uleb128_33");
+            }
             let mut p = le_u8(input)?.1;
             v |= (p as u32 & 0x7f) << (sh as u32);
             (input, p) = le_u8(input)?;
@@ -72,6 +95,7 @@ pub fn lj_header(input: &[u8]) -> IResult<&[u8], LuaHeader, ErrorTree<&[u8]>> {
                 number_size: 4,
                 number_integral: false,
                 lj_flags,
+                types_version: 0,
             })
             .context("luajit1"),
             map(tuple((tag(b"\x02"), be_u8)), |(_, lj_flags)| LuaHeader {
@@ -84,6 +108,7 @@ pub fn lj_header(input: &[u8]) -> IResult<&[u8], LuaHeader, ErrorTree<&[u8]>> {
                 number_size: 4,
                 number_integral: false,
                 lj_flags,
+                types_version: 0,
             })
             .context("luajit2"),
         )),
@@ -96,21 +121,24 @@ pub fn lj_complex_constant<'a, 'h>(
     protos: &'h RefCell<Vec<LuaChunk>>,
     endian: Endianness,
 ) -> impl Parser<&'a [u8], LuaConstant, ErrorTree<&'a [u8]>> + 'h {
-    move |input| {
-        let (input, ty) = leb128_u64(input)?;
+    move |start| {
+        let (input, ty) = leb128_u64(start)?;
+        let uint64 = || {
+            map(tuple((leb128_u32, leb128_u32)), |(lo, hi)| {
+                lo as u64 | ((hi as u64) << 32)
+            })
+        };
+        let number = || {
+            map(tuple((leb128_u32, leb128_u32)), |(lo, hi)| {
+                combine_number(lo, hi, endian)
+            })
+        };
         Ok(match ty {
-            BCDUMP_KGC_I64 => map(
-                tuple((nom_leb128::leb128_u32, nom_leb128::leb128_u32)),
-                |(lo, hi)| LuaConstant::Number(LuaNumber::Integer(lo as i64 | ((hi as i64) << 32))),
-            )(input)?,
-            BCDUMP_KGC_U64 => map(
-                tuple((nom_leb128::leb128_u32, nom_leb128::leb128_u32)),
-                |(lo, hi)| {
-                    LuaConstant::Number(LuaNumber::Integer(
-                        (lo as u64 | ((hi as u64) << 32)) as i64,
-                    ))
-                },
-            )(input)?,
+            BCDUMP_KGC_I64 => map(uint64(), |n| LuaConstant::Int64(n as i64))(input)?,
+            BCDUMP_KGC_U64 => map(uint64(), LuaConstant::UInt64)(input)?,
+            BCDUMP_KGC_COMPLEX => map(tuple((number(), number())), |(re, im)| {
+                LuaConstant::Complex { re, im }
+            })(input)?,
             BCDUMP_KGC_TAB => lj_tab(endian).context("read table").parse(input)?,
             BCDUMP_KGC_CHILD => match stack.borrow_mut().pop() {
                 Some(proto) => {
@@ -125,7 +153,15 @@ pub fn lj_complex_constant<'a, 'h>(
                 let (input, s) = take(len as usize)(input)?;
                 (input, LuaConstant::from(s.to_vec()))
             }
-            _ => unreachable!("BCDUMP_KGC: {ty}"),
+            _ => fail_with(
+                start,
+                ParseError::InvalidConstant {
+                    offset: 0,
+                    tag: ty as _,
+                    context: vec![],
+                    path: vec![],
+                },
+            )?,
         })
     }
 }
@@ -155,15 +191,16 @@ fn combine_number(lo: u32, hi: u32, endian: Endianness) -> f64 {
 }
 
 pub fn lj_tabk<'a>(endian: Endianness) -> impl Parser<&'a [u8], LuaConstant, ErrorTree<&'a [u8]>> {
-    move |input: &'a [u8]| {
-        let (input, ty) = leb128_usize(input)?;
+    move |start: &'a [u8]| {
+        let (input, ty) = leb128_usize(start)?;
         // println!("tabk: {ty}");
         Ok(match ty {
             BCDUMP_KTAB_NIL => (input, LuaConstant::Null),
             BCDUMP_KTAB_FALSE => (input, LuaConstant::Bool(false)),
             BCDUMP_KTAB_TRUE => (input, LuaConstant::Bool(true)),
+            // the int32 is dumped as an unsigned uleb128
             BCDUMP_KTAB_INT => map(leb128_u32, |n| {
-                LuaConstant::Number(LuaNumber::Integer(n as _))
+                LuaConstant::Number(LuaNumber::Integer(n as i32 as _))
             })(input)?,
             BCDUMP_KTAB_NUM => map(tuple((leb128_u32, leb128_u32)), |(lo, hi)| {
                 LuaConstant::Number(LuaNumber::Float(combine_number(lo, hi, endian)))
@@ -173,7 +210,15 @@ pub fn lj_tabk<'a>(endian: Endianness) -> impl Parser<&'a [u8], LuaConstant, Err
                 let (input, s) = take(len)(input)?;
                 (input, LuaConstant::from(s.to_vec()))
             }
-            _ => unreachable!("BCDUMP_KTAB: {ty}"),
+            _ => fail_with(
+                start,
+                ParseError::InvalidConstant {
+                    offset: 0,
+                    tag: ty as _,
+                    context: vec![],
+                    path: vec![],
+                },
+            )?,
         })
     }
 }
@@ -194,6 +239,79 @@ fn lj_num_constant<'a>(
     }
 }
 
+/// Size of a line offset of the line info, which depends on the number of lines
+fn line_size(numline: u64) -> u8 {
+    match numline {
+        0..=0xff => 1,
+        0x100..=0xffff => 2,
+        _ => 4,
+    }
+}
+
+fn zstring(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    terminated(take_until(&b"\0"[..]), tag(b"\0"))(input)
+}
+
+/// Debug info of `lj_proto`, which is the line offset of each instruction from `line_defined`,
+/// the upvalue names and the variable info. The pcs of the variables count the FUNCF header,
+/// so the first dumped instruction is at pc 1
+fn lj_debuginfo<'a>(
+    input: &'a [u8],
+    header: &LuaHeader,
+    chunk: &mut LuaChunk,
+    numline: u64,
+) -> IResult<&'a [u8], ()> {
+    let line_defined = chunk.line_defined as u32;
+    let endian = header.endian();
+    let (mut input, (source_lines, upvalue_names)) = tuple((
+        count(
+            map(
+                |input| match line_size(numline) {
+                    1 => map(le_u8, u32::from)(input),
+                    2 => map(complete::u16(endian), u32::from)(input),
+                    _ => complete::u32(endian)(input),
+                },
+                |delta| (line_defined.wrapping_add(delta), 0),
+            ),
+            chunk.instructions.len(),
+        )
+        .context("lineinfo"),
+        count(map(zstring, |s| s.to_vec()), chunk.num_upvalues as usize).context("upvalue names"),
+    ))(input)?;
+
+    let mut locals = vec![];
+    let mut last_pc = 0u64;
+    loop {
+        let (rest, b) = le_u8(input)?;
+        let name;
+        (input, name) = match b {
+            VARNAME_END => {
+                input = rest;
+                break;
+            }
+            1..VARNAME__MAX => (rest, VARNAMES[b as usize].to_string()),
+            _ => map(zstring, |s| String::from_utf8_lossy(s).into_owned())
+                .context("varname")
+                .parse(input)?,
+        };
+        let (start, len);
+        (input, (start, len)) = tuple((leb128_u64, leb128_u64))(input)?;
+        let start_pc = last_pc.wrapping_add(start);
+        locals.push(LuaLocal {
+            name,
+            start_pc,
+            end_pc: start_pc.wrapping_add(len),
+            reg: 0,
+        });
+        last_pc = start_pc;
+    }
+
+    chunk.source_lines = source_lines;
+    chunk.upvalue_names = upvalue_names;
+    chunk.locals = locals;
+    Ok((input, ()))
+}
+
 fn lj_proto<'a, 'h>(
     header: &'h LuaHeader,
     stack: &'h RefCell<Vec<LuaChunk>>,
@@ -223,8 +341,11 @@ fn lj_proto<'a, 'h>(
         let mut numline = 0;
         let mut debuginfo_size = 0;
         if !header.test_luajit_flag(FLAG_IS_STRIPPED) {
-            (input, (debuginfo_size, line_defined, numline)) =
-                tuple((leb128_u64, leb128_u64, leb128_u64))(input)?;
+            (input, debuginfo_size) = leb128_u64(input)?;
+            // the lines are only dumped along with the debug info
+            if debuginfo_size > 0 {
+                (input, (line_defined, numline)) = tuple((leb128_u64, leb128_u64))(input)?;
+            }
         }
         let last_line_defined = line_defined + numline;
 
@@ -261,40 +382,42 @@ fn lj_proto<'a, 'h>(
         ))(input)?;
         constants.reverse();
 
+        let mut chunk = LuaChunk {
+            name: vec![],
+            num_upvalues,
+            num_params,
+            line_defined,
+            last_line_defined,
+            flags,
+            instructions,
+            upvalue_infos,
+            constants,
+            num_constants,
+            max_stack: framesize,
+            is_vararg: if flags & PROTO_VARARG != 0 {
+                Some(LuaVarArgInfo::new())
+            } else {
+                None
+            },
+            prototypes: protos.into_inner(),
+            ..Default::default()
+        };
         if debuginfo_size > 0 {
-            (input, _) = take(debuginfo_size as usize)(input)?;
+            let (rest, ()) = lj_debuginfo(input, header, &mut chunk, numline)?;
+            if input.len() - rest.len() > debuginfo_size as usize {
+                return fail_expected(input, "debuginfo size");
+            }
         }
-
-        Ok((
-            input,
-            Some(LuaChunk {
-                name: vec![],
-                num_upvalues,
-                num_params,
-                line_defined,
-                last_line_defined,
-                flags,
-                instructions,
-                upvalue_infos,
-                constants,
-                num_constants,
-                max_stack: framesize,
-                is_vararg: if flags & PROTO_VARARG != 0 {
-                    Some(LuaVarArgInfo::new())
-                } else {
-                    None
-                },
-                prototypes: protos.into_inner(),
-                ..Default::default()
-            }),
-        ))
+        (input, _) = take(debuginfo_size as usize)(input)?;
+        check_depth(input, &chunk)?;
+        Ok((input, Some(chunk)))
     }
 }
 
 pub fn lj_chunk<'h, 'a: 'h>(
     header: &'h LuaHeader,
 ) -> impl Parser<&'a [u8], LuaChunk, ErrorTree<&'a [u8]>> + 'h {
-    move |mut input| {
+    limited(move |mut input| {
         let mut name = &b""[..];
         if !header.test_luajit_flag(FLAG_IS_STRIPPED) {
             let namelen;
@@ -302,7 +425,10 @@ pub fn lj_chunk<'h, 'a: 'h>(
             (input, name) = take(namelen as usize)(input)?;
         }
         let protos = RefCell::new(vec![]);
-        while let (i, Some(proto)) = lj_proto(&header, &protos).parse(input)? {
+        while let (i, Some(proto)) = lj_proto(header, &protos)
+            .parse(input)
+            .map_err(|e| e.map(in_proto(protos.borrow().len(), input)))?
+        {
             protos.borrow_mut().push(proto);
             input = i;
         }
@@ -316,7 +442,236 @@ pub fn lj_chunk<'h, 'a: 'h>(
                 context("stack unbalanced", fail).parse(input)?.1
             },
         ))
+    })
+}
+
+pub fn dump_uleb128(d: &mut DumpState, mut v: u64) {
+    while v >= 0x80 {
+        d.byte((v & 0x7f) as u8 | 0x80);
+        v >>= 7;
+    }
+    d.byte(v as u8);
+}
+
+/// Inverse of `uleb128_33`, the lowest bit of the first byte is the `isnum` flag
+pub fn dump_uleb128_33(d: &mut DumpState, v: u32, isnum: bool) {
+    dump_uleb128(d, (v as u64) << 1 | isnum as u64)
+}
+
+/// Inverse of `combine_number`
+fn split_number(n: f64, endian: Endianness) -> (u32, u32) {
+    let bits = n.to_bits();
+    if endian == Endianness::Big {
+        ((bits >> 32) as u32, bits as u32)
+    } else {
+        (bits as u32, (bits >> 32) as u32)
+    }
+}
+
+/// Inverse of `lj_header`
+pub fn dump_lj_header(d: &mut DumpState) {
+    d.bytes(b"\x1bLJ");
+    d.byte(if d.header.version() == LUAJ1 { 1 } else { 2 });
+    // the endianness follows `big_endian`, in case of a retargeted header
+    let flags = d.header.lj_flags & !FLAG_IS_BIG_ENDIAN;
+    d.byte(
+        flags
+            | if d.header.big_endian {
+                FLAG_IS_BIG_ENDIAN
+            } else {
+                0
+            },
+    );
+}
+
+/// Inverse of `lj_tabk`
+fn dump_lj_tabk(d: &mut DumpState, k: &LuaConstant) -> Result<(), DumpError> {
+    match k {
+        LuaConstant::Null => dump_uleb128(d, BCDUMP_KTAB_NIL as _),
+        LuaConstant::Bool(false) => dump_uleb128(d, BCDUMP_KTAB_FALSE as _),
+        LuaConstant::Bool(true) => dump_uleb128(d, BCDUMP_KTAB_TRUE as _),
+        &LuaConstant::Number(LuaNumber::Integer(i)) if i32::try_from(i).is_ok() => {
+            dump_uleb128(d, BCDUMP_KTAB_INT as _);
+            dump_uleb128(d, i as u32 as _);
+        }
+        &LuaConstant::Number(n) => {
+            let n = match n {
+                LuaNumber::Integer(i) => i as f64,
+                LuaNumber::Float(f) => f,
+            };
+            let (lo, hi) = split_number(n, d.header.endian());
+            dump_uleb128(d, BCDUMP_KTAB_NUM as _);
+            dump_uleb128(d, lo as _);
+            dump_uleb128(d, hi as _);
+        }
+        LuaConstant::String(s) => {
+            dump_uleb128(d, (BCDUMP_KTAB_STR + s.len()) as _);
+            d.bytes(s);
+        }
+        _ => return Err(DumpError::UnsupportedConstant),
+    }
+    Ok(())
+}
+
+/// Inverse of `lj_complex_constant`
+fn dump_lj_complex_constant(d: &mut DumpState, k: &LuaConstant) -> Result<(), DumpError> {
+    match k {
+        LuaConstant::Proto(_) => dump_uleb128(d, BCDUMP_KGC_CHILD),
+        LuaConstant::Table(box ConstTable { array, hash }) => {
+            dump_uleb128(d, BCDUMP_KGC_TAB);
+            dump_uleb128(d, array.len() as _);
+            dump_uleb128(d, hash.len() as _);
+            for k in array {
+                dump_lj_tabk(d, k)?;
+            }
+            for (k, v) in hash {
+                dump_lj_tabk(d, k)?;
+                dump_lj_tabk(d, v)?;
+            }
+        }
+        &LuaConstant::Int64(i) => {
+            dump_uleb128(d, BCDUMP_KGC_I64);
+            dump_uleb128(d, i as u32 as _);
+            dump_uleb128(d, (i as u64 >> 32) as _);
+        }
+        &LuaConstant::UInt64(i) => {
+            dump_uleb128(d, BCDUMP_KGC_U64);
+            dump_uleb128(d, i as u32 as _);
+            dump_uleb128(d, i >> 32);
+        }
+        &LuaConstant::Complex { re, im } => {
+            dump_uleb128(d, BCDUMP_KGC_COMPLEX);
+            for n in [re, im] {
+                let (lo, hi) = split_number(n, d.header.endian());
+                dump_uleb128(d, lo as _);
+                dump_uleb128(d, hi as _);
+            }
+        }
+        LuaConstant::String(s) => {
+            dump_uleb128(d, BCDUMP_KGC_STR + s.len() as u64);
+            d.bytes(s);
+        }
+        _ => return Err(DumpError::UnsupportedConstant),
+    }
+    Ok(())
+}
+
+/// Inverse of `lj_num_constant`, integral numbers in the range of i32 are dumped as integers
+fn dump_lj_num_constant(d: &mut DumpState, n: &LuaNumber) {
+    let n = match *n {
+        LuaNumber::Integer(i) if i32::try_from(i).is_ok() => {
+            return dump_uleb128_33(d, i as u32, false);
+        }
+        LuaNumber::Integer(i) => i as f64,
+        LuaNumber::Float(f) => f,
+    };
+    let (lo, hi) = split_number(n, d.header.endian());
+    dump_uleb128_33(d, lo, true);
+    dump_uleb128(d, hi as _);
+}
+
+/// Inverse of `lj_debuginfo`, all instructions are attributed to `line_defined` if the
+/// chunk has no line of each instruction
+fn dump_lj_debuginfo(d: &mut DumpState, chunk: &LuaChunk, numline: u64) -> Result<(), DumpError> {
+    let size = line_size(numline);
+    for i in 0..chunk.instructions.len() {
+        let line = match chunk.source_lines.get(i) {
+            Some(&(line, _)) if chunk.source_lines.len() == chunk.instructions.len() => line,
+            _ => chunk.line_defined as u32,
+        };
+        d.uint(line.saturating_sub(chunk.line_defined as u32) as u64, size)?;
+    }
+    for i in 0..chunk.num_upvalues as usize {
+        d.bytes(chunk.upvalue_names.get(i).map_or(&[][..], |n| n));
+        d.byte(0);
+    }
+    let mut last_pc = 0;
+    for l in &chunk.locals {
+        match VARNAMES[1..].iter().position(|&n| n == l.name) {
+            Some(i) => d.byte(i as u8 + 1),
+            None => {
+                d.bytes(l.name.as_bytes());
+                d.byte(0);
+            }
+        }
+        dump_uleb128(d, l.start_pc.wrapping_sub(last_pc));
+        dump_uleb128(d, l.end_pc.wrapping_sub(l.start_pc));
+        last_pc = l.start_pc;
+    }
+    d.byte(VARNAME_END);
+    Ok(())
+}
+
+/// Inverse of `lj_proto`, the child prototypes are dumped before their parent
+fn dump_lj_proto(d: &mut DumpState, chunk: &LuaChunk) -> Result<(), DumpError> {
+    // the children are popped from a stack, so they are dumped in the order of the constants
+    for k in &chunk.constants {
+        if let LuaConstant::Proto(i) = k {
+            let child = chunk
+                .prototypes
+                .get(*i)
+                .ok_or(DumpError::Overflow { value: *i as _ })?;
+            dump_lj_proto(d, child)?;
+        }
+    }
+
+    let mut p = DumpState::new(d.header);
+    p.bytes(&[
+        chunk.flags,
+        chunk.num_params,
+        chunk.max_stack,
+        chunk.num_upvalues,
+    ]);
+    dump_uleb128(&mut p, chunk.constants.len() as _);
+    dump_uleb128(&mut p, chunk.num_constants.len() as _);
+    dump_uleb128(&mut p, chunk.instructions.len() as _);
+
+    let mut debuginfo = DumpState::new(d.header);
+    if !d.header.test_luajit_flag(FLAG_IS_STRIPPED) {
+        let numline = chunk
+            .source_lines
+            .iter()
+            .map(|l| l.0 as u64)
+            .chain([chunk.last_line_defined])
+            .max()
+            .unwrap_or_default()
+            .saturating_sub(chunk.line_defined);
+        dump_lj_debuginfo(&mut debuginfo, chunk, numline)?;
+
+        dump_uleb128(&mut p, debuginfo.out.len() as _);
+        dump_uleb128(&mut p, chunk.line_defined);
+        dump_uleb128(&mut p, numline);
+    }
+
+    for &i in &chunk.instructions {
+        p.instruction(i)?;
+    }
+    for u in &chunk.upvalue_infos {
+        p.uint((u.on_stack as u64) << 15 | u.id as u64, 2)?;
+    }
+    for k in chunk.constants.iter().rev() {
+        dump_lj_complex_constant(&mut p, k)?;
     }
+    for n in &chunk.num_constants {
+        dump_lj_num_constant(&mut p, n);
+    }
+    p.bytes(&debuginfo.out);
+
+    dump_uleb128(d, p.out.len() as _);
+    d.bytes(&p.out);
+    Ok(())
+}
+
+/// Inverse of `lj_header` and `lj_chunk`
+pub fn dump_lj_chunk(d: &mut DumpState, chunk: &LuaChunk) -> Result<(), DumpError> {
+    dump_lj_header(d);
+    if !d.header.test_luajit_flag(FLAG_IS_STRIPPED) {
+        dump_uleb128(d, chunk.name.len() as _);
+        d.bytes(&chunk.name);
+    }
+    dump_lj_proto(d, chunk)?;
+    d.byte(0);
+    Ok(())
 }
 
 bitflags::bitflags! {
@@ -329,3 +684,512 @@ bitflags::bitflags! {
         const HAS_ILOOP = 0b0001000;
     }
 }
+
+/// Bias of the D operand of jump instructions
+pub const BCBIAS_J: i32 = 0x8000;
+
+/// Operand mode of luajit bytecode (BCMode in lj_bc.h)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperandMode {
+    None,
+    /// Destination register
+    Dst,
+    /// Base register of a range
+    Base,
+    /// Variable register
+    Var,
+    /// Base register which is only read
+    RBase,
+    /// Upvalue index
+    Uv,
+    /// Unsigned literal
+    Lit,
+    /// Signed literal
+    LitS,
+    /// Primitive type: nil, false or true
+    Pri,
+    /// Index of `LuaChunk::num_constants`
+    Num,
+    /// Index of a string in `LuaChunk::constants`
+    Str,
+    /// Index of a template table in `LuaChunk::constants`
+    Tab,
+    /// Index of a prototype in `LuaChunk::constants`
+    Func,
+    /// Biased jump offset
+    Jump,
+    /// Index of a cdata in `LuaChunk::constants`
+    CData,
+}
+
+impl OperandMode {
+    /// Whether the operand is an index of the complex constants
+    pub fn is_gc_constant(self) -> bool {
+        matches!(self, Self::Str | Self::Tab | Self::Func | Self::CData)
+    }
+}
+
+/// Opcodes of luajit, the discriminants follow the numbering of luajit 2.1
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpCode {
+    IsLt,
+    IsGe,
+    IsLe,
+    IsGt,
+    IsEqV,
+    IsNeV,
+    IsEqS,
+    IsNeS,
+    IsEqN,
+    IsNeN,
+    IsEqP,
+    IsNeP,
+    IsTC,
+    IsFC,
+    IsT,
+    IsF,
+    /// luajit 2.1 only
+    IsType,
+    /// luajit 2.1 only
+    IsNum,
+    Mov,
+    Not,
+    Unm,
+    Len,
+    AddVN,
+    SubVN,
+    MulVN,
+    DivVN,
+    ModVN,
+    AddNV,
+    SubNV,
+    MulNV,
+    DivNV,
+    ModNV,
+    AddVV,
+    SubVV,
+    MulVV,
+    DivVV,
+    ModVV,
+    Pow,
+    Cat,
+    KStr,
+    KCData,
+    KShort,
+    KNum,
+    KPri,
+    KNil,
+    UGet,
+    USetV,
+    USetS,
+    USetN,
+    USetP,
+    UClo,
+    FNew,
+    TNew,
+    TDup,
+    GGet,
+    GSet,
+    TGetV,
+    TGetS,
+    TGetB,
+    /// luajit 2.1 only
+    TGetR,
+    TSetV,
+    TSetS,
+    TSetB,
+    TSetM,
+    /// luajit 2.1 only
+    TSetR,
+    CallM,
+    Call,
+    CallMT,
+    CallT,
+    IterC,
+    IterN,
+    VArg,
+    IsNext,
+    RetM,
+    Ret,
+    Ret0,
+    Ret1,
+    ForI,
+    JForI,
+    ForL,
+    IForL,
+    JForL,
+    IterL,
+    IIterL,
+    JIterL,
+    Loop,
+    ILoop,
+    JLoop,
+    Jmp,
+    FuncF,
+    IFuncF,
+    JFuncF,
+    FuncV,
+    IFuncV,
+    JFuncV,
+    FuncC,
+    FuncCW,
+}
+
+/// Opcodes of luajit 2.0, which lack ISTYPE, ISNUM, TGETR and TSETR
+const BC_LJ20: [OpCode; 93] = [
+    OpCode::IsLt,
+    OpCode::IsGe,
+    OpCode::IsLe,
+    OpCode::IsGt,
+    OpCode::IsEqV,
+    OpCode::IsNeV,
+    OpCode::IsEqS,
+    OpCode::IsNeS,
+    OpCode::IsEqN,
+    OpCode::IsNeN,
+    OpCode::IsEqP,
+    OpCode::IsNeP,
+    OpCode::IsTC,
+    OpCode::IsFC,
+    OpCode::IsT,
+    OpCode::IsF,
+    OpCode::Mov,
+    OpCode::Not,
+    OpCode::Unm,
+    OpCode::Len,
+    OpCode::AddVN,
+    OpCode::SubVN,
+    OpCode::MulVN,
+    OpCode::DivVN,
+    OpCode::ModVN,
+    OpCode::AddNV,
+    OpCode::SubNV,
+    OpCode::MulNV,
+    OpCode::DivNV,
+    OpCode::ModNV,
+    OpCode::AddVV,
+    OpCode::SubVV,
+    OpCode::MulVV,
+    OpCode::DivVV,
+    OpCode::ModVV,
+    OpCode::Pow,
+    OpCode::Cat,
+    OpCode::KStr,
+    OpCode::KCData,
+    OpCode::KShort,
+    OpCode::KNum,
+    OpCode::KPri,
+    OpCode::KNil,
+    OpCode::UGet,
+    OpCode::USetV,
+    OpCode::USetS,
+    OpCode::USetN,
+    OpCode::USetP,
+    OpCode::UClo,
+    OpCode::FNew,
+    OpCode::TNew,
+    OpCode::TDup,
+    OpCode::GGet,
+    OpCode::GSet,
+    OpCode::TGetV,
+    OpCode::TGetS,
+    OpCode::TGetB,
+    OpCode::TSetV,
+    OpCode::TSetS,
+    OpCode::TSetB,
+    OpCode::TSetM,
+    OpCode::CallM,
+    OpCode::Call,
+    OpCode::CallMT,
+    OpCode::CallT,
+    OpCode::IterC,
+    OpCode::IterN,
+    OpCode::VArg,
+    OpCode::IsNext,
+    OpCode::RetM,
+    OpCode::Ret,
+    OpCode::Ret0,
+    OpCode::Ret1,
+    OpCode::ForI,
+    OpCode::JForI,
+    OpCode::ForL,
+    OpCode::IForL,
+    OpCode::JForL,
+    OpCode::IterL,
+    OpCode::IIterL,
+    OpCode::JIterL,
+    OpCode::Loop,
+    OpCode::ILoop,
+    OpCode::JLoop,
+    OpCode::Jmp,
+    OpCode::FuncF,
+    OpCode::IFuncF,
+    OpCode::JFuncF,
+    OpCode::FuncV,
+    OpCode::IFuncV,
+    OpCode::JFuncV,
+    OpCode::FuncC,
+    OpCode::FuncCW,
+];
+
+impl OpCode {
+    pub const NAMES: [&'static str; 97] = [
+        "ISLT", "ISGE", "ISLE", "ISGT", "ISEQV", "ISNEV", "ISEQS", "ISNES", "ISEQN", "ISNEN",
+        "ISEQP", "ISNEP", "ISTC", "ISFC", "IST", "ISF", "ISTYPE", "ISNUM", "MOV", "NOT", "UNM",
+        "LEN", "ADDVN", "SUBVN", "MULVN", "DIVVN", "MODVN", "ADDNV", "SUBNV", "MULNV", "DIVNV",
+        "MODNV", "ADDVV", "SUBVV", "MULVV", "DIVVV", "MODVV", "POW", "CAT", "KSTR", "KCDATA",
+        "KSHORT", "KNUM", "KPRI", "KNIL", "UGET", "USETV", "USETS", "USETN", "USETP", "UCLO",
+        "FNEW", "TNEW", "TDUP", "GGET", "GSET", "TGETV", "TGETS", "TGETB", "TGETR", "TSETV",
+        "TSETS", "TSETB", "TSETM", "TSETR", "CALLM", "CALL", "CALLMT", "CALLT", "ITERC", "ITERN",
+        "VARG", "ISNEXT", "RETM", "RET", "RET0", "RET1", "FORI", "JFORI", "FORL", "IFORL", "JFORL",
+        "ITERL", "IITERL", "JITERL", "LOOP", "ILOOP", "JLOOP", "JMP", "FUNCF", "IFUNCF", "JFUNCF",
+        "FUNCV", "IFUNCV", "JFUNCV", "FUNCC", "FUNCCW",
+    ];
+
+    const MODES: [[OperandMode; 3]; 97] = {
+        use OperandMode::*;
+        [
+            [Var, None, Var],
+            [Var, None, Var],
+            [Var, None, Var],
+            [Var, None, Var],
+            [Var, None, Var],
+            [Var, None, Var],
+            [Var, None, Str],
+            [Var, None, Str],
+            [Var, None, Num],
+            [Var, None, Num],
+            [Var, None, Pri],
+            [Var, None, Pri],
+            [Dst, None, Var],
+            [Dst, None, Var],
+            [None, None, Var],
+            [None, None, Var],
+            [Var, None, Lit],
+            [Var, None, Lit],
+            [Dst, None, Var],
+            [Dst, None, Var],
+            [Dst, None, Var],
+            [Dst, None, Var],
+            [Dst, Var, Num],
+            [Dst, Var, Num],
+            [Dst, Var, Num],
+            [Dst, Var, Num],
+            [Dst, Var, Num],
+            [Dst, Var, Num],
+            [Dst, Var, Num],
+            [Dst, Var, Num],
+            [Dst, Var, Num],
+            [Dst, Var, Num],
+            [Dst, Var, Var],
+            [Dst, Var, Var],
+            [Dst, Var, Var],
+            [Dst, Var, Var],
+            [Dst, Var, Var],
+            [Dst, Var, Var],
+            [Dst, RBase, RBase],
+            [Dst, None, Str],
+            [Dst, None, CData],
+            [Dst, None, LitS],
+            [Dst, None, Num],
+            [Dst, None, Pri],
+            [Base, None, Base],
+            [Dst, None, Uv],
+            [Uv, None, Var],
+            [Uv, None, Str],
+            [Uv, None, Num],
+            [Uv, None, Pri],
+            [RBase, None, Jump],
+            [Dst, None, Func],
+            [Dst, None, Lit],
+            [Dst, None, Tab],
+            [Dst, None, Str],
+            [Var, None, Str],
+            [Dst, Var, Var],
+            [Dst, Var, Str],
+            [Dst, Var, Lit],
+            [Dst, Var, Var],
+            [Var, Var, Var],
+            [Var, Var, Str],
+            [Var, Var, Lit],
+            [Base, None, Num],
+            [Var, Var, Var],
+            [Base, Lit, Lit],
+            [Base, Lit, Lit],
+            [Base, None, Lit],
+            [Base, None, Lit],
+            [Base, Lit, Lit],
+            [Base, Lit, Lit],
+            [Base, Lit, Lit],
+            [Base, None, Jump],
+            [Base, None, Lit],
+            [RBase, None, Lit],
+            [RBase, None, Lit],
+            [RBase, None, Lit],
+            [Base, None, Jump],
+            [Base, None, Jump],
+            [Base, None, Jump],
+            [Base, None, Jump],
+            [Base, None, Lit],
+            [Base, None, Jump],
+            [Base, None, Jump],
+            [Base, None, Lit],
+            [RBase, None, Jump],
+            [RBase, None, Jump],
+            [RBase, None, Lit],
+            [RBase, None, Jump],
+            [RBase, None, None],
+            [RBase, None, None],
+            [RBase, None, Lit],
+            [RBase, None, None],
+            [RBase, None, None],
+            [RBase, None, Lit],
+            [RBase, None, None],
+            [RBase, None, None],
+        ]
+    };
+
+    /// Decode the opcode byte, the numbering depends on the luajit version of the header
+    pub fn from_u8(op: u8, header: &LuaHeader) -> Option<Self> {
+        if header.version() == LUAJ1 {
+            BC_LJ20.get(op as usize).copied()
+        } else if (op as usize) < Self::NAMES.len() {
+            Some(unsafe { core::mem::transmute::<u8, Self>(op) })
+        } else {
+            None
+        }
+    }
+
+    /// Encode the opcode byte for the luajit version of the header
+    pub fn to_u8(self, header: &LuaHeader) -> Option<u8> {
+        if header.version() == LUAJ1 {
+            BC_LJ20.iter().position(|&op| op == self).map(|i| i as u8)
+        } else {
+            Some(self as u8)
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+
+    /// Operand modes of A, B and C/D
+    pub fn modes(self) -> [OperandMode; 3] {
+        Self::MODES[self as usize]
+    }
+
+    pub fn a_mode(self) -> OperandMode {
+        self.modes()[0]
+    }
+
+    pub fn b_mode(self) -> OperandMode {
+        self.modes()[1]
+    }
+
+    /// Mode of the last operand, which is C if B is used, otherwise D
+    pub fn cd_mode(self) -> OperandMode {
+        self.modes()[2]
+    }
+}
+
+/// Decoded luajit instruction, B and C are the high and low byte of D
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub op: OpCode,
+    pub a: u8,
+    pub d: u16,
+}
+
+impl Instruction {
+    pub fn decode(i: u32, header: &LuaHeader) -> Option<Self> {
+        Some(Self {
+            op: OpCode::from_u8(i as u8, header)?,
+            a: (i >> 8) as u8,
+            d: (i >> 16) as u16,
+        })
+    }
+
+    pub fn b(&self) -> u8 {
+        (self.d >> 8) as u8
+    }
+
+    pub fn c(&self) -> u8 {
+        self.d as u8
+    }
+
+    /// The last operand, which is C if B is used, otherwise D
+    pub fn cd(&self) -> u16 {
+        if self.op.b_mode() == OperandMode::None {
+            self.d
+        } else {
+            self.c() as u16
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.op.name()
+    }
+
+    /// The jump target of the instruction located at `pc`
+    pub fn jump_target(&self, pc: usize) -> Option<usize> {
+        if self.op.cd_mode() != OperandMode::Jump {
+            return None;
+        }
+        usize::try_from(pc as i64 + 1 + self.d as i64 - BCBIAS_J as i64).ok()
+    }
+
+    /// The complex constant referenced by a str/tab/func/cdata operand
+    pub fn gc_constant<'a>(&self, chunk: &'a LuaChunk) -> Option<&'a LuaConstant> {
+        self.op
+            .cd_mode()
+            .is_gc_constant()
+            .then(|| chunk.constants.get(self.cd() as usize))
+            .flatten()
+    }
+
+    /// The numeric constant referenced by a num operand
+    pub fn num_constant<'a>(&self, chunk: &'a LuaChunk) -> Option<&'a LuaNumber> {
+        (self.op.cd_mode() == OperandMode::Num)
+            .then(|| chunk.num_constants.get(self.cd() as usize))
+            .flatten()
+    }
+
+    /// The child prototype referenced by FNEW
+    pub fn prototype<'a>(&self, chunk: &'a LuaChunk) -> Option<&'a LuaChunk> {
+        match self.gc_constant(chunk)? {
+            LuaConstant::Proto(i) => chunk.prototypes.get(*i),
+            _ => None,
+        }
+    }
+
+    /// The value of a pri operand
+    pub fn primitive(&self) -> Option<LuaConstant> {
+        if self.op.cd_mode() != OperandMode::Pri {
+            return None;
+        }
+        match self.d {
+            0 => Some(LuaConstant::Null),
+            1 => Some(LuaConstant::Bool(false)),
+            2 => Some(LuaConstant::Bool(true)),
+            _ => None,
+        }
+    }
+}
+
+/// Decode all instructions of a chunk into `(pc, instruction)` pairs
+pub fn decode(
+    header: &LuaHeader,
+    chunk: &LuaChunk,
+) -> Result<Vec<(usize, Instruction)>, DecodeError> {
+    chunk
+        .instructions
+        .iter()
+        .enumerate()
+        .map(|(pc, &raw)| {
+            Instruction::decode(raw, header)
+                .map(|i| (pc, i))
+                .ok_or(DecodeError::InvalidOpcode { pc, raw })
+        })
+        .collect()
+}
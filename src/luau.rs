@@ -1,4 +1,6 @@
-use nom::{multi::count, number::complete::le_u8};
+use std::collections::HashMap;
+
+use nom::{combinator::verify, number::complete::le_u8};
 
 use super::*;
 
@@ -9,6 +11,9 @@ pub fn varint(mut input: &[u8]) -> IResult<&[u8], usize> {
         let (rest, b) = le_u8(input)?;
         input = rest;
 
+        if shift >= usize::BITS as usize {
+            return fail_expected(input, "varint");
+        }
         x |= ((b & 0x7f) as usize) << shift;
         shift += 7;
 
@@ -21,13 +26,12 @@ pub fn varint(mut input: &[u8]) -> IResult<&[u8], usize> {
 }
 
 pub fn string<'a>(input: &'a [u8], stable: &[Rc<ByteBuf>]) -> IResult<&'a [u8], Rc<ByteBuf>> {
-    map(varint, |i| {
-        if i > 0 {
-            stable[i - 1].clone()
-        } else {
-            Rc::new(ByteBuf::new())
-        }
-    })(input)
+    let (rest, i) = varint(input)?;
+    match i.checked_sub(1).map(|i| stable.get(i)) {
+        None => Ok((rest, Rc::new(ByteBuf::new()))),
+        Some(Some(s)) => Ok((rest, s.clone())),
+        Some(None) => fail_expected(input, "string index"),
+    }
 }
 
 pub const LBC_CONSTANT_NIL: u8 = 0;
@@ -37,171 +41,1365 @@ pub const LBC_CONSTANT_STRING: u8 = 3;
 pub const LBC_CONSTANT_IMPORT: u8 = 4;
 pub const LBC_CONSTANT_TABLE: u8 = 5;
 pub const LBC_CONSTANT_CLOSURE: u8 = 6;
+pub const LBC_CONSTANT_VECTOR: u8 = 7;
+pub const LBC_CONSTANT_TABLE_WITH_CONSTANTS: u8 = 8;
+pub const LBC_CONSTANT_INTEGER: u8 = 9;
 
-pub fn table<'a>(mut input: &'a [u8], k: &[LuaConstant]) -> IResult<&'a [u8], ConstTable> {
+/// Table template, only the keys are stored and the values are nil, or the index of the value
+/// for `LBC_CONSTANT_TABLE_WITH_CONSTANTS` where -1 is nil
+pub fn table<'a>(
+    mut input: &'a [u8],
+    k: &[LuaConstant],
+    with_constants: bool,
+) -> IResult<&'a [u8], ConstTable> {
     let numk;
     (input, numk) = varint(input)?;
-    let mut result = ConstTable {
-        hash: Vec::with_capacity(numk),
-        ..Default::default()
-    };
+    check_count(
+        input,
+        numk,
+        core::mem::size_of::<(LuaConstant, LuaConstant)>(),
+    )?;
+    let mut result = ConstTable::default();
     for _ in 0..numk {
-        let ik;
-        (input, ik) = varint(input)?;
-        result
-            .hash
-            .push((k[ik].clone(), LuaConstant::Number(LuaNumber::Integer(0))));
+        let (rest, ik) = varint(input)?;
+        let Some(key) = k.get(ik) else {
+            return fail_expected(input, "constant index");
+        };
+        let (rest, value) = if with_constants {
+            let (rest, iv) = complete::le_i32(rest)?;
+            match usize::try_from(iv).map(|iv| k.get(iv)) {
+                Err(_) if iv == -1 => (rest, LuaConstant::Null),
+                Ok(Some(value)) => (rest, value.clone()),
+                _ => return fail_expected(input, "constant index"),
+            }
+        } else {
+            (rest, LuaConstant::Null)
+        };
+        result.hash.push((key.clone(), value));
+        input = rest;
     }
     Ok((input, result))
 }
 
+/// Import of a global path like `math.floor`, the id packs the count in the top 2 bits and up to
+/// 3 indices of string constants in 10 bits each
+pub fn import<'a>(input: &'a [u8], k: &[LuaConstant]) -> IResult<&'a [u8], LuaConstant> {
+    let (rest, id) = complete::le_u32(input)?;
+    let count = id >> 30;
+    let mut path = vec![];
+    for i in 0..count {
+        let ik = (id >> (20 - i * 10)) & 1023;
+        match k.get(ik as usize) {
+            Some(LuaConstant::String(s)) => path.push(s.clone()),
+            _ => return fail_expected(input, "import path"),
+        }
+    }
+    Ok((rest, LuaConstant::Import { id, path }))
+}
+
 pub fn constants<'a>(
     mut input: &'a [u8],
     stable: &[Rc<ByteBuf>],
 ) -> IResult<&'a [u8], Vec<LuaConstant>> {
     let num;
     (input, num) = varint(input)?;
-    let mut result = Vec::with_capacity(num);
+    check_count(input, num, core::mem::size_of::<LuaConstant>())?;
+    let mut result = vec![];
     for _ in 0..num {
-        let ty;
+        let (rest, ty) = le_u8(input)?;
         let k;
-        (input, ty) = le_u8(input)?;
         (input, k) = match ty {
-            LBC_CONSTANT_NIL => Ok((input, LuaConstant::Null)),
-            LBC_CONSTANT_BOOLEAN => map(le_u8, |b| LuaConstant::Bool(b != 0))(input),
+            LBC_CONSTANT_NIL => Ok((rest, LuaConstant::Null)),
+            LBC_CONSTANT_BOOLEAN => map(le_u8, |b| LuaConstant::Bool(b != 0))(rest),
             LBC_CONSTANT_NUMBER => map(complete::f64(Endianness::Little), |n| {
                 LuaConstant::Number(LuaNumber::Float(n))
-            })(input),
-            LBC_CONSTANT_STRING => map(|i| string(i, stable), LuaConstant::String)(input),
-            // LBC_CONSTANT_IMPORT => map(complete::be_u32, |i| LuaConstant::Imp(i as _))(input),
-            LBC_CONSTANT_IMPORT => map(complete::be_u32, |_| LuaConstant::Null)(input),
-            LBC_CONSTANT_TABLE => {
-                let (input, t) = table(input, &result)?;
+            })(rest),
+            LBC_CONSTANT_STRING => map(|i| string(i, stable), LuaConstant::String)(rest),
+            LBC_CONSTANT_IMPORT => import(rest, &result),
+            LBC_CONSTANT_TABLE | LBC_CONSTANT_TABLE_WITH_CONSTANTS => {
+                let with_constants = ty == LBC_CONSTANT_TABLE_WITH_CONSTANTS;
+                let (input, t) = table(rest, &result, with_constants)?;
                 Ok((input, LuaConstant::Table(t.into())))
             }
-            LBC_CONSTANT_CLOSURE => map(varint, LuaConstant::Proto)(input),
+            LBC_CONSTANT_CLOSURE => map(varint, LuaConstant::Proto)(rest),
+            LBC_CONSTANT_VECTOR => map(count(complete::le_f32, 4), |v| {
+                LuaConstant::Vector([v[0], v[1], v[2], v[3]])
+            })(rest),
+            // the sign followed by the magnitude
+            LBC_CONSTANT_INTEGER => map(tuple((le_u8, varint)), |(neg, n)| {
+                let n = n as i64;
+                LuaConstant::Number(LuaNumber::Integer(if neg != 0 {
+                    n.wrapping_neg()
+                } else {
+                    n
+                }))
+            })(rest),
             // _ => context("string", fail::<&u8, LuaConstant, _>).parse(input),
-            _ => unreachable!("const type: {ty}"),
+            _ => fail_with(
+                input,
+                ParseError::InvalidConstant {
+                    offset: 0,
+                    tag: ty,
+                    context: vec![],
+                    path: vec![],
+                },
+            ),
         }?;
         result.push(k);
     }
     Ok((input, result))
 }
 
-pub fn bytecode(input: &[u8]) -> IResult<&[u8], LuaChunk> {
-    let (mut input, _version) = le_u8(input)?;
-    let mut types_version = 0;
-
-    if _version >= 4 {
-        let (input2, _types_version) = le_u8(input)?;
-        types_version = _types_version;
-        input = input2
-    }
-
-    // string table
-    let (input, stable) = length_count(
-        varint,
-        map(
-            |input| {
-                let (input, n) = varint(input)?;
-                context("string", take(n))(input)
+/// Header of luau bytecode, which is only the version bytes
+pub fn header(version: u8, types_version: u8) -> LuaHeader {
+    LuaHeader {
+        lua_version: LUAU.0,
+        format_version: version,
+        int_size: 4,
+        instruction_size: 4,
+        number_size: 8,
+        types_version,
+        ..Default::default()
+    }
+}
+
+/// As luau bytecode has no signature, the version bytes are checked strictly to tell it from
+/// other formats
+pub fn luau_header(input: &[u8]) -> IResult<&[u8], LuaHeader> {
+    let (input, version) = verify(le_u8, |v| (LBC_VERSION_MIN..=LBC_VERSION_MAX).contains(v))
+        .context("luau version")
+        .parse(input)?;
+    let (input, types_version) = if version >= 4 {
+        verify(le_u8, |v| {
+            (LBC_TYPE_VERSION_MIN..=LBC_TYPE_VERSION_MAX).contains(v)
+        })
+        .context("luau types version")
+        .parse(input)?
+    } else {
+        (input, 0)
+    };
+    Ok((input, header(version, types_version)))
+}
+
+/// The luau compiler outputs the version 0 followed by the error message when compiling fails,
+/// returns the message if `input` is such an output
+pub fn compile_error_message(input: &[u8]) -> Option<&str> {
+    let message = std::str::from_utf8(input.strip_prefix(b"\0")?).ok()?;
+    (!message.is_empty() && !message.contains('\0')).then_some(message)
+}
+
+/// Fails with [`ParseError::LuauCompileError`] on the output of a failed compilation, see
+/// [`compile_error_message`]
+pub fn compile_error(input: &[u8]) -> IResult<&[u8], LuaHeader> {
+    let (rest, _) = tag(b"\0")(input)?;
+    match compile_error_message(input) {
+        Some(message) => fail_with(
+            rest,
+            ParseError::LuauCompileError {
+                message: message.into(),
             },
-            |s| Rc::new(ByteBuf::from(s.to_vec())),
         ),
-    )(input)?;
+        None => fail_expected(input, "luau compile error"),
+    }
+}
 
-    // proto table
-    let (mut input, num) = varint(input)?;
-    let mut protos = Vec::with_capacity(num);
+pub fn bytecode(input: &[u8]) -> IResult<&[u8], LuaChunk> {
+    let (_, version) = le_u8(input)?;
+    if version == 0 {
+        compile_error(input)?;
+    }
+    if !(LBC_VERSION_MIN..=LBC_VERSION_MAX).contains(&version) {
+        return fail_with(input, ParseError::UnsupportedVersion { offset: 0, version });
+    }
+    let (input, header) = luau_header(input)?;
+    let result = lua_chunk(&header).parse(input);
+    result
+}
 
-    let string = |i| string(i, &stable);
+/// Like `bytecode` for opcodes encoded by a `BytecodeEncoder`, the instructions of the result
+/// are decoded to the plain opcodes. The encoding is detected if `encoding` is `None`
+pub fn bytecode_with(input: &[u8], encoding: Option<OpcodeEncoding>) -> IResult<&[u8], LuaChunk> {
+    let (_, version) = le_u8(input)?;
+    let (rest, mut chunk) = bytecode(input)?;
+    let Some(encoding) = encoding.or_else(|| OpcodeEncoding::detect(&chunk, version)) else {
+        return fail_expected(input, "opcode encoding");
+    };
+    if decode_opcodes(&mut chunk, version, encoding).is_err() {
+        return fail_expected(input, "instruction");
+    }
+    Ok((rest, chunk))
+}
 
-    for _ in 0..num {
-        let (mut input1, (max_stack, num_params, num_upvalues, is_vararg)) =
-            tuple((be_u8, be_u8, be_u8, be_u8))(input)?;
+/// Replace the encoded opcode bytes of the chunk and its children with the plain ones
+pub fn decode_opcodes(
+    chunk: &mut LuaChunk,
+    version: u8,
+    encoding: OpcodeEncoding,
+) -> Result<(), DecodeError> {
+    let code = Instructions::new(&chunk.instructions, version)
+        .with_encoding(encoding)
+        .collect::<Result<Vec<_>, _>>()?;
+    for (pc, insn) in code {
+        chunk.instructions[pc] = insn.raw;
+    }
+    for p in &mut chunk.prototypes {
+        decode_opcodes(p, version, encoding)?;
+    }
+    Ok(())
+}
+
+/// Line of each instruction, like `luau_getline` it is the absolute line of the interval
+/// `pc >> linegaplog2` plus the accumulated byte offset of the instruction
+pub fn lineinfo(input: &[u8], n: usize) -> IResult<&[u8], Vec<(u32, u32)>> {
+    let (input, linegaplog2) = le_u8(input)?;
+    let interval = |pc: usize| pc.checked_shr(linegaplog2 as _).unwrap_or(0);
+    let intervals = n.checked_sub(1).map_or(0, |pc| interval(pc) + 1);
+    let (input, (offsets, lines)) =
+        tuple((count(le_u8, n), count(complete::le_i32, intervals)))(input)?;
+
+    let lines = lines
+        .into_iter()
+        .scan(0i32, |line, delta| {
+            *line = line.wrapping_add(delta);
+            Some(*line)
+        })
+        .collect::<Vec<_>>();
+    let mut offset = 0u8;
+    let source_lines = offsets
+        .into_iter()
+        .enumerate()
+        .map(|(pc, delta)| {
+            offset = offset.wrapping_add(delta);
+            (lines[interval(pc)].wrapping_add(offset as i32) as u32, 0)
+        })
+        .collect();
+    Ok((input, source_lines))
+}
 
-        if _version >= 4 {
-            let (input2, _flags) = be_u8(input1)?;
-            let (mut input2, types_size) = varint(input2)?;
+pub fn lua_chunk<'h, 'a: 'h>(
+    header: &'h LuaHeader,
+) -> impl Parser<&'a [u8], LuaChunk, ErrorTree<&'a [u8]>> + 'h {
+    limited(move |input| {
+        let version = header.format_version;
 
-            if types_size > 0 && types_version == 1 {
-                for _ in 0..types_size {
-                    let (input3, _byte) = le_u8(input2)?;
-                    input2 = input3
+        // string table
+        let (input, stable) = length_count(
+            varint,
+            map(
+                |input| {
+                    let (input, n) = varint(input)?;
+                    context("string", take(n))(input)
+                },
+                |s| Rc::new(ByteBuf::from(s.to_vec())),
+            ),
+        )(input)?;
+
+        // the names of userdata types are not kept, the tagged types refer to them by index
+        let input = if header.types_version == 3 {
+            userdata_types(input, &stable)?.0
+        } else {
+            input
+        };
+
+        // proto table
+        let (mut input, num) = varint(input)?;
+        check_count(input, num, core::mem::size_of::<LuaChunk>())?;
+        let mut protos = vec![];
+
+        let string = |i| string(i, &stable);
+
+        for _ in 0..num {
+            let (mut input1, (max_stack, num_params, num_upvalues, is_vararg)) =
+                tuple((be_u8, be_u8, be_u8, be_u8))(input)?;
+
+            let mut flags = 0;
+            let mut types = None;
+            if version >= 4 {
+                let (input2, (f, types_size)) = tuple((le_u8, varint))(input1)?;
+                let (input2, data) = context("type info", take(types_size))(input2)?;
+                if types_size > 0 {
+                    let (_, t) = type_info(data, header.types_version)?;
+                    types = Some(t);
                 }
+                flags = f;
+                input1 = input2;
             }
 
-            input1 = input2;
-        }
-
-        let (mut input1, (instructions, constants, prototypes, line_defined, name, has_lineinfo)) =
-            tuple((
+            let (
+                mut input1,
+                (instructions, constants, prototypes, line_defined, name, has_lineinfo),
+            ) = tuple((
                 length_count(varint, complete::u32(Endianness::Little)),
                 |i| constants(i, stable.as_slice()),
-                length_count(varint, map(varint, |i| core::mem::take(&mut protos[i]))),
+                length_count(varint, |input| {
+                    let (rest, i) = varint(input)?;
+                    // a proto can only be the child of one parent
+                    match protos.get_mut(i).and_then(Option::take) {
+                        Some(p) => Ok((rest, p)),
+                        None => fail_expected(input, "proto index"),
+                    }
+                }),
                 map(varint, |n| n as u64),
                 string,
                 le_u8,
             ))(input1)?;
 
-        if has_lineinfo > 0 {
-            let (input2, linegaplog2) = be_u8(input1)?;
-            let intervals = ((instructions.len() - 1) >> (linegaplog2 as usize)) + 1;
-            let (input2, _lineinfo) = count(be_u8, instructions.len())(input2)?;
-            let (input2, _abslineinfo) = count(complete::be_i32, intervals)(input2)?;
-            input1 = input2;
-        }
-
-        let (mut input1, has_debuginfo) = le_u8(input1)?;
-        let mut locals = vec![];
-        let mut upvalue_names = vec![];
-        if has_debuginfo > 0 {
-            (input1, (locals, upvalue_names)) = tuple((
-                length_count(
-                    varint,
-                    map(
-                        tuple((string, varint, varint, le_u8)),
-                        |(name, start, end, reg)| LuaLocal {
-                            name: String::from_utf8_lossy(name.as_slice()).into(),
-                            start_pc: start as _,
-                            end_pc: end as _,
-                            reg,
-                        },
+            let mut source_lines = vec![];
+            if has_lineinfo > 0 {
+                (input1, source_lines) = lineinfo(input1, instructions.len())?;
+            }
+
+            let (mut input1, has_debuginfo) = le_u8(input1)?;
+            let mut locals = vec![];
+            let mut upvalue_names = vec![];
+            if has_debuginfo > 0 {
+                (input1, (locals, upvalue_names)) = tuple((
+                    length_count(
+                        varint,
+                        map(
+                            tuple((string, varint, varint, le_u8)),
+                            |(name, start, end, reg)| LuaLocal {
+                                name: String::from_utf8_lossy(name.as_slice()).into(),
+                                start_pc: start as _,
+                                end_pc: end as _,
+                                reg,
+                            },
+                        ),
                     ),
-                ),
-                length_count(varint, map(string, |s| s.as_ref().clone().into_vec())),
-            ))(input1)?;
+                    length_count(varint, map(string, |s| s.as_ref().clone().into_vec())),
+                ))(input1)?;
+            }
+
+            input = input1;
+            let proto = LuaChunk {
+                name: name.to_vec(),
+                line_defined,
+                // not stored by luau, the last line of the instructions is the closest
+                last_line_defined: source_lines.iter().map(|l| l.0 as u64).max().unwrap_or(0),
+                num_upvalues,
+                num_params,
+                max_stack,
+                flags,
+                type_info: types,
+                prototypes,
+                is_vararg: if is_vararg > 0 {
+                    Some(LuaVarArgInfo {
+                        has_arg: true,
+                        needs_arg: true,
+                    })
+                } else {
+                    None
+                },
+                instructions,
+                constants,
+                source_lines,
+                locals,
+                upvalue_names,
+                ..Default::default()
+            };
+            check_depth(input, &proto)?;
+            protos.push(Some(proto));
+        }
+
+        let (rest, mainid) = varint(input)?;
+        match protos.get_mut(mainid).and_then(Option::take) {
+            Some(main) => Ok((rest, main)),
+            None => fail_expected(input, "main proto index"),
         }
+    })
+}
+
+pub const LBC_VERSION_MIN: u8 = 3;
+pub const LBC_VERSION_MAX: u8 = 6;
+pub const LBC_TYPE_VERSION_MIN: u8 = 1;
+pub const LBC_TYPE_VERSION_MAX: u8 = 3;
+
+bitflags::bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct ProtoFlags: u8 {
+        /// The module is compiled with `--!native`
+        const NATIVE_MODULE = 0b00000001;
+        /// The function is not worth compiling natively
+        const NATIVE_COLD = 0b00000010;
+        /// The function is marked with `@native`
+        const NATIVE_FUNCTION = 0b00000100;
+    }
+}
+
+pub const LBC_TYPE_NIL: u8 = 0;
+pub const LBC_TYPE_BOOLEAN: u8 = 1;
+pub const LBC_TYPE_NUMBER: u8 = 2;
+pub const LBC_TYPE_STRING: u8 = 3;
+pub const LBC_TYPE_TABLE: u8 = 4;
+pub const LBC_TYPE_FUNCTION: u8 = 5;
+pub const LBC_TYPE_THREAD: u8 = 6;
+pub const LBC_TYPE_USERDATA: u8 = 7;
+pub const LBC_TYPE_VECTOR: u8 = 8;
+pub const LBC_TYPE_BUFFER: u8 = 9;
+pub const LBC_TYPE_ANY: u8 = 15;
+pub const LBC_TYPE_TAGGED_USERDATA_BASE: u8 = 64;
+pub const LBC_TYPE_TAGGED_USERDATA_END: u8 = 64 + 32;
+pub const LBC_TYPE_OPTIONAL_BIT: u8 = 1 << 7;
+
+/// Type annotation of the bytecode, the ones unknown to this version are kept as is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Encode, Decode)]
+pub enum BytecodeType {
+    Nil,
+    Boolean,
+    Number,
+    String,
+    Table,
+    Function,
+    Thread,
+    UserData,
+    Vector,
+    Buffer,
+    Any,
+    /// Index of the userdata type registered by the embedder
+    TaggedUserData(u8),
+    Unknown(u8),
+}
 
-        input = input1;
-        let proto = LuaChunk {
-            name: name.to_vec(),
-            line_defined,
-            last_line_defined: 0,
-            num_upvalues,
-            num_params,
-            max_stack,
-            prototypes,
-            is_vararg: if is_vararg > 0 {
-                Some(LuaVarArgInfo {
-                    has_arg: true,
-                    needs_arg: true,
-                })
-            } else {
-                None
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Encode, Decode)]
+pub struct TypeAnnotation {
+    pub ty: BytecodeType,
+    pub optional: bool,
+}
+
+impl From<u8> for TypeAnnotation {
+    fn from(b: u8) -> Self {
+        let ty = match b & !LBC_TYPE_OPTIONAL_BIT {
+            LBC_TYPE_NIL => BytecodeType::Nil,
+            LBC_TYPE_BOOLEAN => BytecodeType::Boolean,
+            LBC_TYPE_NUMBER => BytecodeType::Number,
+            LBC_TYPE_STRING => BytecodeType::String,
+            LBC_TYPE_TABLE => BytecodeType::Table,
+            LBC_TYPE_FUNCTION => BytecodeType::Function,
+            LBC_TYPE_THREAD => BytecodeType::Thread,
+            LBC_TYPE_USERDATA => BytecodeType::UserData,
+            LBC_TYPE_VECTOR => BytecodeType::Vector,
+            LBC_TYPE_BUFFER => BytecodeType::Buffer,
+            LBC_TYPE_ANY => BytecodeType::Any,
+            t @ LBC_TYPE_TAGGED_USERDATA_BASE..LBC_TYPE_TAGGED_USERDATA_END => {
+                BytecodeType::TaggedUserData(t - LBC_TYPE_TAGGED_USERDATA_BASE)
+            }
+            t => BytecodeType::Unknown(t),
+        };
+        Self {
+            ty,
+            optional: b & LBC_TYPE_OPTIONAL_BIT != 0,
+        }
+    }
+}
+
+impl From<TypeAnnotation> for u8 {
+    fn from(t: TypeAnnotation) -> Self {
+        let b = match t.ty {
+            BytecodeType::Nil => LBC_TYPE_NIL,
+            BytecodeType::Boolean => LBC_TYPE_BOOLEAN,
+            BytecodeType::Number => LBC_TYPE_NUMBER,
+            BytecodeType::String => LBC_TYPE_STRING,
+            BytecodeType::Table => LBC_TYPE_TABLE,
+            BytecodeType::Function => LBC_TYPE_FUNCTION,
+            BytecodeType::Thread => LBC_TYPE_THREAD,
+            BytecodeType::UserData => LBC_TYPE_USERDATA,
+            BytecodeType::Vector => LBC_TYPE_VECTOR,
+            BytecodeType::Buffer => LBC_TYPE_BUFFER,
+            BytecodeType::Any => LBC_TYPE_ANY,
+            BytecodeType::TaggedUserData(i) => LBC_TYPE_TAGGED_USERDATA_BASE + i,
+            BytecodeType::Unknown(t) => t,
+        };
+        if t.optional {
+            b | LBC_TYPE_OPTIONAL_BIT
+        } else {
+            b
+        }
+    }
+}
+
+/// Type of a register in the pc range
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct TypedLocal {
+    pub ty: TypeAnnotation,
+    pub reg: u8,
+    pub start_pc: u64,
+    pub end_pc: u64,
+}
+
+/// Type information of a proto, the upvalues and locals are available since types version 2
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct TypeInfo {
+    /// Types of the parameters from the function signature
+    pub args: Vec<TypeAnnotation>,
+    pub upvalues: Vec<TypeAnnotation>,
+    pub locals: Vec<TypedLocal>,
+}
+
+/// Function signature, which is the function type followed by the parameter types
+fn signature(input: &[u8]) -> IResult<&[u8], Vec<TypeAnnotation>> {
+    let (rest, ty) = le_u8(input)?;
+    if ty != LBC_TYPE_FUNCTION {
+        return fail_expected(input, "function type");
+    }
+    length_count(le_u8, map(le_u8, TypeAnnotation::from))(rest)
+}
+
+/// Type information of `types_size` bytes, which is only the signature for types version 1
+pub fn type_info(input: &[u8], types_version: u8) -> IResult<&[u8], TypeInfo> {
+    if types_version == 1 {
+        let (_, args) = signature(input)?;
+        return Ok((
+            &input[input.len()..],
+            TypeInfo {
+                args,
+                ..Default::default()
             },
-            instructions,
-            constants,
+        ));
+    }
+
+    let (input, (sig_size, num_upvalues, num_locals)) = tuple((varint, varint, varint))(input)?;
+    let (input, sig) = take(sig_size)(input)?;
+    let (_, args) = signature(sig)?;
+    let (input, (upvalues, locals)) = tuple((
+        count(map(le_u8, TypeAnnotation::from), num_upvalues),
+        count(
+            map(
+                tuple((le_u8, le_u8, varint, varint)),
+                |(ty, reg, start_pc, len)| TypedLocal {
+                    ty: ty.into(),
+                    reg,
+                    start_pc: start_pc as _,
+                    end_pc: start_pc as u64 + len as u64,
+                },
+            ),
+            num_locals,
+        ),
+    ))(input)?;
+    Ok((
+        input,
+        TypeInfo {
+            args,
+            upvalues,
             locals,
-            upvalue_names,
-            ..Default::default()
+        },
+    ))
+}
+
+/// Names of the userdata types registered by the embedder for types version 3, as
+/// `(index, string)` pairs terminated by index 0
+pub fn userdata_types<'a>(
+    mut input: &'a [u8],
+    stable: &[Rc<ByteBuf>],
+) -> IResult<&'a [u8], Vec<(u8, Rc<ByteBuf>)>> {
+    let mut result = vec![];
+    loop {
+        let (rest, index) = le_u8(input)?;
+        input = rest;
+        if index == 0 {
+            break;
+        }
+        let name;
+        (input, name) = string(input, stable)?;
+        result.push((index, name));
+    }
+    Ok((input, result))
+}
+
+/// Opcodes of luau, the discriminants follow the numbering of the latest bytecode version
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpCode {
+    Nop,
+    Break,
+    LoadNil,
+    LoadB,
+    LoadN,
+    LoadK,
+    Move,
+    GetGlobal,
+    SetGlobal,
+    GetUpval,
+    SetUpval,
+    CloseUpvals,
+    GetImport,
+    GetTable,
+    SetTable,
+    GetTableKS,
+    SetTableKS,
+    GetTableN,
+    SetTableN,
+    NewClosure,
+    NameCall,
+    Call,
+    Return,
+    Jump,
+    JumpBack,
+    JumpIf,
+    JumpIfNot,
+    JumpIfEq,
+    JumpIfLe,
+    JumpIfLt,
+    JumpIfNotEq,
+    JumpIfNotLe,
+    JumpIfNotLt,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    AddK,
+    SubK,
+    MulK,
+    DivK,
+    ModK,
+    PowK,
+    And,
+    Or,
+    AndK,
+    OrK,
+    Concat,
+    Not,
+    Minus,
+    Length,
+    NewTable,
+    DupTable,
+    SetList,
+    ForNPrep,
+    ForNLoop,
+    ForGLoop,
+    ForGPrepINext,
+    FastCall3,
+    ForGPrepNext,
+    NativeCall,
+    GetVarArgs,
+    DupClosure,
+    PrepVarArgs,
+    LoadKX,
+    JumpX,
+    FastCall,
+    Coverage,
+    Capture,
+    SubRK,
+    DivRK,
+    FastCall1,
+    FastCall2,
+    FastCall2K,
+    ForGPrep,
+    JumpXEqKNil,
+    JumpXEqKB,
+    JumpXEqKN,
+    JumpXEqKS,
+    IDiv,
+    IDivK,
+    // deprecated opcodes, which are encoded as FASTCALL3, NATIVECALL, SUBRK and DIVRK
+    // in bytecode older than version 6
+    DepForGLoopINext,
+    DepForGLoopNext,
+    DepJumpIfEqK,
+    DepJumpIfNotEqK,
+}
+
+impl OpCode {
+    pub const NAMES: [&'static str; 87] = [
+        "NOP",
+        "BREAK",
+        "LOADNIL",
+        "LOADB",
+        "LOADN",
+        "LOADK",
+        "MOVE",
+        "GETGLOBAL",
+        "SETGLOBAL",
+        "GETUPVAL",
+        "SETUPVAL",
+        "CLOSEUPVALS",
+        "GETIMPORT",
+        "GETTABLE",
+        "SETTABLE",
+        "GETTABLEKS",
+        "SETTABLEKS",
+        "GETTABLEN",
+        "SETTABLEN",
+        "NEWCLOSURE",
+        "NAMECALL",
+        "CALL",
+        "RETURN",
+        "JUMP",
+        "JUMPBACK",
+        "JUMPIF",
+        "JUMPIFNOT",
+        "JUMPIFEQ",
+        "JUMPIFLE",
+        "JUMPIFLT",
+        "JUMPIFNOTEQ",
+        "JUMPIFNOTLE",
+        "JUMPIFNOTLT",
+        "ADD",
+        "SUB",
+        "MUL",
+        "DIV",
+        "MOD",
+        "POW",
+        "ADDK",
+        "SUBK",
+        "MULK",
+        "DIVK",
+        "MODK",
+        "POWK",
+        "AND",
+        "OR",
+        "ANDK",
+        "ORK",
+        "CONCAT",
+        "NOT",
+        "MINUS",
+        "LENGTH",
+        "NEWTABLE",
+        "DUPTABLE",
+        "SETLIST",
+        "FORNPREP",
+        "FORNLOOP",
+        "FORGLOOP",
+        "FORGPREP_INEXT",
+        "FASTCALL3",
+        "FORGPREP_NEXT",
+        "NATIVECALL",
+        "GETVARARGS",
+        "DUPCLOSURE",
+        "PREPVARARGS",
+        "LOADKX",
+        "JUMPX",
+        "FASTCALL",
+        "COVERAGE",
+        "CAPTURE",
+        "SUBRK",
+        "DIVRK",
+        "FASTCALL1",
+        "FASTCALL2",
+        "FASTCALL2K",
+        "FORGPREP",
+        "JUMPXEQKNIL",
+        "JUMPXEQKB",
+        "JUMPXEQKN",
+        "JUMPXEQKS",
+        "IDIV",
+        "IDIVK",
+        "DEP_FORGLOOP_INEXT",
+        "DEP_FORGLOOP_NEXT",
+        "DEP_JUMPIFEQK",
+        "DEP_JUMPIFNOTEQK",
+    ];
+
+    /// Decode the opcode byte, some opcodes are reused across bytecode versions
+    pub fn from_u8(op: u8, version: u8) -> Option<Self> {
+        use OpCode::*;
+
+        Some(match op {
+            60 if version < 6 => DepForGLoopINext,
+            62 if version < 6 => DepForGLoopNext,
+            71 if version < 6 => DepJumpIfEqK,
+            72 if version < 6 => DepJumpIfNotEqK,
+            0..=82 => unsafe { core::mem::transmute::<u8, Self>(op) },
+            _ => return None,
+        })
+    }
+
+    /// Encode the opcode byte
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::DepForGLoopINext => 60,
+            Self::DepForGLoopNext => 62,
+            Self::DepJumpIfEqK => 71,
+            Self::DepJumpIfNotEqK => 72,
+            op => op as u8,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+
+    /// Whether the instruction is followed by an AUX word
+    pub fn has_aux(self) -> bool {
+        use OpCode::*;
+
+        matches!(
+            self,
+            GetGlobal
+                | SetGlobal
+                | GetImport
+                | GetTableKS
+                | SetTableKS
+                | NameCall
+                | JumpIfEq
+                | JumpIfLe
+                | JumpIfLt
+                | JumpIfNotEq
+                | JumpIfNotLe
+                | JumpIfNotLt
+                | NewTable
+                | SetList
+                | ForGLoop
+                | LoadKX
+                | FastCall2
+                | FastCall2K
+                | FastCall3
+                | JumpXEqKNil
+                | JumpXEqKB
+                | JumpXEqKN
+                | JumpXEqKS
+                | DepJumpIfEqK
+                | DepJumpIfNotEqK
+        )
+    }
+
+    /// Whether D is a jump offset
+    pub fn is_jump_d(self) -> bool {
+        use OpCode::*;
+
+        matches!(
+            self,
+            Jump | JumpBack
+                | JumpIf
+                | JumpIfNot
+                | JumpIfEq
+                | JumpIfLe
+                | JumpIfLt
+                | JumpIfNotEq
+                | JumpIfNotLe
+                | JumpIfNotLt
+                | ForNPrep
+                | ForNLoop
+                | ForGPrep
+                | ForGLoop
+                | ForGPrepINext
+                | ForGPrepNext
+                | JumpXEqKNil
+                | JumpXEqKB
+                | JumpXEqKN
+                | JumpXEqKS
+                | DepJumpIfEqK
+                | DepJumpIfNotEqK
+                | DepForGLoopINext
+                | DepForGLoopNext
+        )
+    }
+
+    pub fn is_fastcall(self) -> bool {
+        use OpCode::*;
+
+        matches!(
+            self,
+            FastCall | FastCall1 | FastCall2 | FastCall2K | FastCall3
+        )
+    }
+}
+
+/// Multiplicative encoding of the opcode bytes applied by a `BytecodeEncoder`, the encoded byte
+/// is `op * multiplier` modulo 256
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OpcodeEncoding {
+    multiplier: u8,
+    inverse: u8,
+}
+
+impl OpcodeEncoding {
+    /// The plain opcodes
+    pub const NONE: Self = Self {
+        multiplier: 1,
+        inverse: 1,
+    };
+    /// The encoding of the well-known Roblox client
+    pub const ROBLOX: Self = Self {
+        multiplier: 227,
+        inverse: 203,
+    };
+
+    /// The multiplier must be odd to be invertible
+    pub fn new(multiplier: u8) -> Option<Self> {
+        if multiplier.is_multiple_of(2) {
+            return None;
+        }
+        // each newton iteration doubles the number of correct bits
+        let mut inverse = multiplier;
+        for _ in 0..3 {
+            inverse = inverse.wrapping_mul(2u8.wrapping_sub(multiplier.wrapping_mul(inverse)));
+        }
+        Some(Self {
+            multiplier,
+            inverse,
+        })
+    }
+
+    pub fn multiplier(self) -> u8 {
+        self.multiplier
+    }
+
+    pub fn encode(self, op: u8) -> u8 {
+        op.wrapping_mul(self.multiplier)
+    }
+
+    pub fn decode(self, byte: u8) -> u8 {
+        byte.wrapping_mul(self.inverse)
+    }
+
+    /// Find the encoding for which the code of every proto decodes and ends with RETURN, the
+    /// plain opcodes and the Roblox encoding are tried first
+    pub fn detect(chunk: &LuaChunk, version: u8) -> Option<Self> {
+        fn valid(chunk: &LuaChunk, version: u8, encoding: OpcodeEncoding) -> bool {
+            let code = Instructions::new(&chunk.instructions, version).with_encoding(encoding);
+            let mut last = None;
+            for insn in code {
+                match insn {
+                    Ok((_, insn)) => last = Some(insn.op),
+                    Err(_) => return false,
+                }
+            }
+            matches!(last, None | Some(OpCode::Return))
+                && chunk.prototypes.iter().all(|p| valid(p, version, encoding))
+        }
+
+        [Self::NONE, Self::ROBLOX]
+            .into_iter()
+            .chain((3..=255).step_by(2).filter_map(Self::new))
+            .find(|&e| valid(chunk, version, e))
+    }
+}
+
+impl Default for OpcodeEncoding {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// Decoded luau instruction with its optional AUX word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub op: OpCode,
+    pub raw: u32,
+    pub aux: Option<u32>,
+}
+
+impl Instruction {
+    pub fn a(&self) -> u8 {
+        (self.raw >> 8) as u8
+    }
+
+    pub fn b(&self) -> u8 {
+        (self.raw >> 16) as u8
+    }
+
+    pub fn c(&self) -> u8 {
+        (self.raw >> 24) as u8
+    }
+
+    pub fn d(&self) -> i16 {
+        (self.raw >> 16) as i16
+    }
+
+    pub fn e(&self) -> i32 {
+        self.raw as i32 >> 8
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.op.name()
+    }
+
+    /// Number of words taken by the instruction
+    pub fn size(&self) -> usize {
+        1 + self.aux.is_some() as usize
+    }
+
+    /// The jump target of the instruction located at `pc`, for FASTCALL it is the
+    /// instruction following the CALL which is skipped on success, C counts from the
+    /// FASTCALL word even if an AUX word follows. LOADB jumps by C when it is not 0
+    pub fn jump_target(&self, pc: usize) -> Option<usize> {
+        let target = if self.op.is_jump_d() {
+            pc as i64 + 1 + self.d() as i64
+        } else if self.op == OpCode::JumpX {
+            pc as i64 + 1 + self.e() as i64
+        } else if self.op == OpCode::LoadB && self.c() != 0 {
+            pc as i64 + 1 + self.c() as i64
+        } else if self.op.is_fastcall() {
+            pc as i64 + self.c() as i64 + 2
+        } else {
+            return None;
         };
-        protos.push(proto);
+        usize::try_from(target).ok()
+    }
+}
+
+/// Iterator over the instructions of luau code, yields `(pc, instruction)` pairs where
+/// the pc counts words, so the AUX words are skipped
+pub struct Instructions<'a> {
+    code: &'a [u32],
+    pc: usize,
+    version: u8,
+    encoding: OpcodeEncoding,
+}
+
+impl<'a> Instructions<'a> {
+    pub fn new(code: &'a [u32], version: u8) -> Self {
+        Self {
+            code,
+            pc: 0,
+            version,
+            encoding: OpcodeEncoding::NONE,
+        }
+    }
+
+    /// Decode the opcode bytes with `encoding`, the `raw` word of the instructions has the
+    /// plain opcode
+    pub fn with_encoding(self, encoding: OpcodeEncoding) -> Self {
+        Self { encoding, ..self }
+    }
+}
+
+impl Iterator for Instructions<'_> {
+    type Item = Result<(usize, Instruction), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pc = self.pc;
+        let raw = *self.code.get(pc)?;
+        // stop at the first error
+        self.pc = self.code.len();
+        let Some(op) = OpCode::from_u8(self.encoding.decode(raw as u8), self.version) else {
+            return Some(Err(DecodeError::InvalidOpcode { pc, raw }));
+        };
+        let raw = (raw & !0xff) | self.encoding.decode(raw as u8) as u32;
+        let aux = if op.has_aux() {
+            match self.code.get(pc + 1) {
+                Some(&aux) => Some(aux),
+                None => return Some(Err(DecodeError::Truncated { pc })),
+            }
+        } else {
+            None
+        };
+        let insn = Instruction { op, raw, aux };
+        self.pc = pc + insn.size();
+        Some(Ok((pc, insn)))
+    }
+}
+
+/// Decode all instructions of a chunk into `(pc, instruction)` pairs, `version` is the bytecode version
+pub fn decode(chunk: &LuaChunk, version: u8) -> Result<Vec<(usize, Instruction)>, DecodeError> {
+    Instructions::new(&chunk.instructions, version).collect()
+}
+
+pub fn dump_varint(d: &mut DumpState, mut x: usize) {
+    while x >= 0x80 {
+        d.byte((x & 0x7f) as u8 | 0x80);
+        x >>= 7;
+    }
+    d.byte(x as u8);
+}
+
+/// Deduplicated string table, the strings are referenced by their 1-based index
+#[derive(Default)]
+pub struct StringTable<'a> {
+    pub strings: Vec<&'a [u8]>,
+    index: HashMap<&'a [u8], usize>,
+}
+
+impl<'a> StringTable<'a> {
+    /// Intern a string and return its index
+    pub fn add(&mut self, s: &'a [u8]) -> usize {
+        if let Some(&i) = self.index.get(s) {
+            return i;
+        }
+        self.strings.push(s);
+        self.index.insert(s, self.strings.len());
+        self.strings.len()
+    }
+
+    /// Like `add`, but the empty string is referenced by 0, as the optional names in the debug info
+    pub fn add_name(&mut self, s: &'a [u8]) -> usize {
+        if s.is_empty() {
+            0
+        } else {
+            self.add(s)
+        }
+    }
+
+    pub fn get(&self, s: &[u8]) -> usize {
+        self.index.get(s).copied().unwrap_or(0)
     }
 
-    let (input, mainid) = varint(input)?;
-    let main = core::mem::take(&mut protos[mainid]);
-    assert!(!main.is_empty());
+    /// Like `get`, but the empty string is 0 even if it is interned for a constant
+    pub fn get_name(&self, s: &[u8]) -> usize {
+        if s.is_empty() {
+            0
+        } else {
+            self.get(s)
+        }
+    }
+}
+
+/// Flatten the prototypes in dependency order, children are placed before their parent,
+/// returns the id of `chunk`
+fn flatten_protos<'a>(
+    chunk: &'a LuaChunk,
+    protos: &mut Vec<(&'a LuaChunk, Vec<usize>)>,
+    strings: &mut StringTable<'a>,
+) -> usize {
+    let children = chunk
+        .prototypes
+        .iter()
+        .map(|p| flatten_protos(p, protos, strings))
+        .collect();
+
+    for k in &chunk.constants {
+        if let LuaConstant::String(s) = k {
+            strings.add(s);
+        }
+    }
+    strings.add_name(&chunk.name);
+    for l in &chunk.locals {
+        strings.add_name(l.name.as_bytes());
+    }
+    for n in &chunk.upvalue_names {
+        strings.add_name(n);
+    }
+
+    protos.push((chunk, children));
+    protos.len() - 1
+}
+
+fn dump_constants(
+    d: &mut DumpState,
+    k: &[LuaConstant],
+    strings: &StringTable,
+) -> Result<(), DumpError> {
+    dump_varint(d, k.len());
+    for (i, c) in k.iter().enumerate() {
+        match c {
+            LuaConstant::Null => d.byte(LBC_CONSTANT_NIL),
+            &LuaConstant::Bool(b) => d.bytes(&[LBC_CONSTANT_BOOLEAN, b as u8]),
+            &LuaConstant::Number(LuaNumber::Float(n)) => {
+                d.byte(LBC_CONSTANT_NUMBER);
+                d.bytes(&n.to_le_bytes());
+            }
+            &LuaConstant::Number(LuaNumber::Integer(n)) => {
+                d.bytes(&[LBC_CONSTANT_INTEGER, (n < 0) as u8]);
+                dump_varint(d, n.unsigned_abs() as usize);
+            }
+            LuaConstant::Vector(v) => {
+                d.byte(LBC_CONSTANT_VECTOR);
+                for x in v {
+                    d.bytes(&x.to_le_bytes());
+                }
+            }
+            LuaConstant::String(s) => {
+                d.byte(LBC_CONSTANT_STRING);
+                dump_varint(d, strings.get(s));
+            }
+            LuaConstant::Table(box ConstTable { array, hash }) if array.is_empty() => {
+                // the keys and values refer to the preceding constants
+                let index = |c| {
+                    k[..i]
+                        .iter()
+                        .position(|k| k == c)
+                        .ok_or(DumpError::UnsupportedConstant)
+                };
+                let with_constants = hash.iter().any(|(_, v)| v != &LuaConstant::Null);
+                d.byte(if with_constants {
+                    LBC_CONSTANT_TABLE_WITH_CONSTANTS
+                } else {
+                    LBC_CONSTANT_TABLE
+                });
+                dump_varint(d, hash.len());
+                for (key, value) in hash {
+                    dump_varint(d, index(key)?);
+                    if with_constants {
+                        let iv = match value {
+                            LuaConstant::Null => -1,
+                            value => index(value)? as i32,
+                        };
+                        d.bytes(&iv.to_le_bytes());
+                    }
+                }
+            }
+            LuaConstant::Import { id, .. } => {
+                d.byte(LBC_CONSTANT_IMPORT);
+                d.bytes(&id.to_le_bytes());
+            }
+            &LuaConstant::Proto(p) => {
+                d.byte(LBC_CONSTANT_CLOSURE);
+                dump_varint(d, p);
+            }
+            _ => return Err(DumpError::UnsupportedConstant),
+        }
+    }
+    Ok(())
+}
+
+/// Inverse of `type_info`
+pub fn dump_type_info(t: &TypeInfo, types_version: u8) -> Vec<u8> {
+    let mut sig = vec![LBC_TYPE_FUNCTION, t.args.len() as u8];
+    sig.extend(t.args.iter().map(|&a| u8::from(a)));
+    if types_version == 1 {
+        return sig;
+    }
+
+    let header = header(LBC_VERSION_MAX, types_version);
+    let mut d = DumpState::new(&header);
+    dump_varint(&mut d, sig.len());
+    dump_varint(&mut d, t.upvalues.len());
+    dump_varint(&mut d, t.locals.len());
+    d.bytes(&sig);
+    for &u in &t.upvalues {
+        d.byte(u.into());
+    }
+    for l in &t.locals {
+        d.bytes(&[l.ty.into(), l.reg]);
+        dump_varint(&mut d, l.start_pc as _);
+        dump_varint(&mut d, l.end_pc.saturating_sub(l.start_pc) as _);
+    }
+    d.out
+}
+
+/// Inverse of `lineinfo`, the interval size is chosen like the luau compiler, as large as
+/// possible while the lines of each interval are within 255 of its first line
+pub fn dump_lineinfo(d: &mut DumpState, source_lines: &[(u32, u32)]) {
+    let lines = source_lines.iter().map(|l| l.0 as i32).collect::<Vec<_>>();
+
+    let mut span = 1usize << 24;
+    let mut offset = 0;
+    while offset < lines.len() {
+        let mut next = offset;
+        let (mut min, mut max) = (lines[offset], lines[offset]);
+        while next < lines.len() && next < offset + span {
+            min = min.min(lines[next]);
+            max = max.max(lines[next]);
+            if max as i64 - min as i64 > 255 {
+                break;
+            }
+            next += 1;
+        }
+        if next < lines.len() && next - offset < span {
+            span = 1 << (next - offset).ilog2();
+        }
+        offset += span;
+    }
+
+    let linegaplog2 = span.ilog2();
+    let mut baselines = vec![i32::MAX; ((lines.len() - 1) >> linegaplog2) + 1];
+    for (pc, &line) in lines.iter().enumerate() {
+        let base = &mut baselines[pc >> linegaplog2];
+        *base = (*base).min(line);
+    }
+
+    d.byte(linegaplog2 as u8);
+    let mut last = 0u8;
+    for (pc, &line) in lines.iter().enumerate() {
+        let offset = line.wrapping_sub(baselines[pc >> linegaplog2]) as u8;
+        d.byte(offset.wrapping_sub(last));
+        last = offset;
+    }
+    let mut last = 0i32;
+    for base in baselines {
+        d.bytes(&base.wrapping_sub(last).to_le_bytes());
+        last = base;
+    }
+}
+
+/// Inverse of `bytecode`, the prototypes are written in dependency order with the main one last.
+///
+/// Closure constants are written as is, so they stay valid as long as the proto ids were
+/// assigned in the same order, which is the case for the output of the luau compiler
+pub fn dump_bytecode(
+    chunk: &LuaChunk,
+    version: u8,
+    types_version: u8,
+) -> Result<Vec<u8>, DumpError> {
+    let header = header(version, types_version);
+    let mut d = DumpState::new(&header);
+    dump_chunk(&mut d, chunk)?;
+    Ok(d.out)
+}
+
+/// Write the bytecode in the version of the header
+pub fn dump_chunk(d: &mut DumpState, chunk: &LuaChunk) -> Result<(), DumpError> {
+    let version = d.header.format_version;
+    if !(LBC_VERSION_MIN..=LBC_VERSION_MAX).contains(&version) {
+        return Err(DumpError::UnsupportedVersion { version });
+    }
+
+    let mut protos = vec![];
+    let mut strings = StringTable::default();
+    let mainid = flatten_protos(chunk, &mut protos, &mut strings);
+
+    d.byte(version);
+    if version >= 4 {
+        d.byte(d.header.types_version);
+    }
+
+    dump_varint(d, strings.strings.len());
+    for s in &strings.strings {
+        dump_varint(d, s.len());
+        d.bytes(s);
+    }
+    if d.header.types_version == 3 {
+        // no userdata types
+        d.byte(0);
+    }
+
+    dump_varint(d, protos.len());
+    for (p, children) in &protos {
+        d.bytes(&[
+            p.max_stack,
+            p.num_params,
+            p.num_upvalues,
+            p.is_vararg.is_some() as u8,
+        ]);
+        if version >= 4 {
+            d.byte(p.flags);
+            let types = p
+                .type_info
+                .as_ref()
+                .map(|t| dump_type_info(t, d.header.types_version))
+                .unwrap_or_default();
+            dump_varint(d, types.len());
+            d.bytes(&types);
+        }
+
+        dump_varint(d, p.instructions.len());
+        for &i in &p.instructions {
+            d.instruction(i)?;
+        }
+        dump_constants(d, &p.constants, &strings)?;
+        dump_varint(d, children.len());
+        for &c in children {
+            dump_varint(d, c);
+        }
+        dump_varint(d, p.line_defined as _);
+        dump_varint(d, strings.get_name(&p.name));
+        if p.source_lines.len() == p.instructions.len() && !p.source_lines.is_empty() {
+            d.byte(1);
+            dump_lineinfo(d, &p.source_lines);
+        } else {
+            d.byte(0);
+        }
+
+        if p.locals.is_empty() && p.upvalue_names.is_empty() {
+            d.byte(0);
+        } else {
+            d.byte(1);
+            dump_varint(d, p.locals.len());
+            for l in &p.locals {
+                dump_varint(d, strings.get_name(l.name.as_bytes()));
+                dump_varint(d, l.start_pc as _);
+                dump_varint(d, l.end_pc as _);
+                d.byte(l.reg);
+            }
+            dump_varint(d, p.upvalue_names.len());
+            for n in &p.upvalue_names {
+                dump_varint(d, strings.get_name(n));
+            }
+        }
+    }
 
-    Ok((input, main))
+    dump_varint(d, mainid);
+    Ok(())
 }
@@ -53,8 +53,17 @@ impl LuaConstant {
             Self::Number(LuaNumber::Integer(i)) => i.to_string(),
             Self::Null => "nil".into(),
             Self::Proto(i) => format!("function<{i}>"),
+            Self::Int64(i) => format!("{i}LL"),
+            Self::UInt64(i) => format!("{i}ULL"),
+            Self::Complex { re, im } if *re == 0.0 => format!("{im}i"),
+            Self::Complex { re, im } => format!("({re}{im:+}i)"),
             Self::Table { .. } => "{}".into(),
-            // Self::Imp(imp) => format!("imp<{imp}>"),
+            Self::Vector([x, y, z, w]) => format!("vector({x}, {y}, {z}, {w})"),
+            Self::Import { path, .. } => path
+                .iter()
+                .map(|s| String::from_utf8_lossy(s))
+                .collect::<Vec<_>>()
+                .join("."),
         }
     }
 }
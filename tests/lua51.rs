@@ -43,3 +43,280 @@ fn test_int() {
     assert_eq!(str_print_1337.as_slice(), b"print number 1337: ");
     assert_eq!(*num_1337, 1337);
 }
+
+#[test]
+fn test_decode() {
+    use luac_parser::lua51::{decode, Instruction, RK};
+
+    let parsed = luac_parser::parse(&std::fs::read("tests/lua51/concat-duble.luac").unwrap()).unwrap();
+    let code = decode(&parsed.main_chunk).unwrap();
+    println!("{code:#?}");
+
+    assert_eq!(code.len(), parsed.main_chunk.instructions.len());
+    assert_eq!(code[0].1, Instruction::GetGlobal { a: 0, bx: 0 });
+    assert_eq!(code[1].1, Instruction::LoadK { a: 1, bx: 1 });
+    assert_eq!(code[3].1, Instruction::Concat { a: 1, b: 1, c: 2 });
+    assert_eq!(code[4].1, Instruction::Call { a: 0, b: 2, c: 1 });
+    assert_eq!(code.last().unwrap().1, Instruction::Return { a: 0, b: 1 });
+    assert_eq!(code[0].1.name(), "GETGLOBAL");
+    assert_eq!(RK::new(0x101), RK::Constant(1));
+    assert_eq!(RK::new(0x101).to_isk(), -2);
+}
+
+#[test]
+fn test_any_instruction() {
+    use luac_parser::instruction::{decode, AnyInstruction, ConstantRef, Instruction, Registers};
+    use luac_parser::{lua51, lua54};
+
+    let parsed = luac_parser::parse(&std::fs::read("tests/lua51/concat-duble.luac").unwrap()).unwrap();
+    let code = decode(&parsed.header, &parsed.main_chunk).unwrap();
+
+    assert_eq!(code[0].1.name(), "GETGLOBAL");
+    assert_eq!(code[0].1.writes(), [Registers::Range(0, 1)]);
+    assert_eq!(code[0].1.constants(), [ConstantRef::Constant(0)]);
+    assert!(code[4].1.is_call());
+    assert!(code[4].1.writes().is_empty());
+    assert!(code.last().unwrap().1.is_return());
+    assert!(!code.last().unwrap().1.falls_through());
+    assert!(code.iter().all(|(pc, i)| i.jump_targets(*pc).is_empty()));
+
+    // LOADBOOL with C skips the next instruction unconditionally
+    let skip = AnyInstruction::Lua51(lua51::Instruction::LoadBool { a: 0, b: 1, c: 1 });
+    assert_eq!(skip.jump_targets(3), [5]);
+    assert!(!skip.falls_through());
+    let load = AnyInstruction::Lua51(lua51::Instruction::LoadBool { a: 0, b: 1, c: 0 });
+    assert!(load.jump_targets(3).is_empty());
+    assert!(load.falls_through());
+    let skip = AnyInstruction::Lua54(lua54::Instruction::LFalseSkip { a: 0 });
+    assert_eq!(skip.jump_targets(3), [5]);
+    assert!(!skip.falls_through());
+}
+
+#[test]
+fn test_writes_overflow() {
+    use luac_parser::instruction::{AnyInstruction, Instruction};
+    use luac_parser::{lua51, lua52, lua53, lua54, luajit, luau};
+
+    // the highest registers of decodable instructions must not overflow
+    let code = [
+        AnyInstruction::Lua51(lua51::Instruction::ForLoop { a: 255, sbx: 0 }),
+        AnyInstruction::Lua51(lua51::Instruction::TForLoop { a: 255, c: 511 }),
+        AnyInstruction::Lua52(lua52::Instruction::TForCall { a: 255, c: 511 }),
+        AnyInstruction::Lua53(lua53::Instruction::ForLoop { a: 255, sbx: 0 }),
+        AnyInstruction::Lua54(lua54::Instruction::TForCall { a: 255, c: 255 }),
+        AnyInstruction::Lua54(lua54::Instruction::LoadNil { a: 255, b: 255 }),
+        AnyInstruction::LuaJit(luajit::Instruction {
+            op: luajit::OpCode::ForL,
+            a: 255,
+            d: 0,
+        }),
+        AnyInstruction::LuaJit(luajit::Instruction {
+            op: luajit::OpCode::KNil,
+            a: 255,
+            d: 0xffff,
+        }),
+        AnyInstruction::Luau(luau::Instruction {
+            op: luau::OpCode::ForGLoop,
+            raw: 0xff00,
+            aux: Some(0xff),
+        }),
+    ];
+    for i in code {
+        assert!(!i.writes().is_empty(), "{i:?}");
+    }
+}
+
+#[test]
+fn test_listing() {
+    let parsed = luac_parser::parse(&std::fs::read("tests/lua51/concat-duble.luac").unwrap()).unwrap();
+    let listing = luac_parser::listing::luac(&parsed, false).unwrap();
+    print!("{listing}");
+
+    let lines = listing.lines().collect::<Vec<_>>();
+    assert!(lines[1].starts_with("main <?:0,0> (21 instructions, 84 bytes at "));
+    assert_eq!(lines[2], "0+ params, 3 slots, 0 upvalues, 0 locals, 9 constants, 0 functions");
+    assert_eq!(lines[3], "\t1\t[-]\tGETGLOBAL\t0 -1\t; print");
+    assert_eq!(lines[4], "\t2\t[-]\tLOADK    \t1 -2\t; \"print number 1: \"");
+    assert_eq!(lines[6], "\t4\t[-]\tCONCAT   \t1 1 2");
+    assert_eq!(lines.last(), Some(&"\t21\t[-]\tRETURN   \t0 1"));
+}
+
+#[test]
+fn test_dump() {
+    for path in ["tests/lua51/concat-duble.luac", "tests/lua51/concat-int.luac"] {
+        let data = std::fs::read(path).unwrap();
+        let parsed = luac_parser::parse(&data).unwrap();
+        assert_eq!(parsed.dump().unwrap(), data);
+    }
+
+    // an empty string constant is not NULL, only the source name may be
+    let data = std::fs::read("tests/lua51/concat-duble.luac").unwrap();
+    let mut parsed = luac_parser::parse(&data).unwrap();
+    parsed.main_chunk.constants[1] = "".into();
+    let data = parsed.dump().unwrap();
+    let mut empty = vec![4];
+    empty.extend(1u64.to_le_bytes());
+    empty.extend([0, 3]);
+    assert!(data.windows(empty.len()).any(|w| w == empty));
+    assert_eq!(luac_parser::parse(&data).unwrap().dump().unwrap(), data);
+}
+
+#[test]
+fn test_retarget() {
+    use luac_parser::{retarget, DumpError, LuaHeader};
+
+    let int = luac_parser::parse(&std::fs::read("tests/lua51/concat-int.luac").unwrap()).unwrap();
+    let duble = std::fs::read("tests/lua51/concat-duble.luac").unwrap();
+    let mut parsed = luac_parser::parse(&duble).unwrap();
+    assert_eq!(retarget(&int, &parsed.header).unwrap(), duble);
+
+    let header = LuaHeader {
+        big_endian: true,
+        int_size: 8,
+        size_t_size: 8,
+        number_size: 4,
+        ..parsed.header.clone()
+    };
+    let data = retarget(&parsed, &header).unwrap();
+    assert_eq!(&data[6..12], [0, 8, 8, 4, 4, 0]);
+    let converted = luac_parser::parse(&data).unwrap();
+    assert_eq!(converted.header, header);
+    assert_eq!(converted.main_chunk.instructions, parsed.main_chunk.instructions);
+    assert_eq!(retarget(&converted, &parsed.header).unwrap(), duble);
+
+    // integral numbers of 8 bytes hold -2^63 but not 2^63
+    let int8 = LuaHeader {
+        number_size: 8,
+        ..int.header.clone()
+    };
+    let mut bounds = luac_parser::parse(&duble).unwrap();
+    bounds.main_chunk.constants = vec![LuaConstant::Number(LuaNumber::Float(-(2f64.powi(63))))];
+    assert!(retarget(&bounds, &int8).is_ok());
+    bounds.main_chunk.constants = vec![LuaConstant::Number(LuaNumber::Float(2f64.powi(63)))];
+    assert_eq!(retarget(&bounds, &int8), Err(DumpError::Inexact));
+
+    parsed.main_chunk.constants.push(LuaConstant::Number(LuaNumber::Float(0.5)));
+    assert_eq!(retarget(&parsed, &int.header), Err(DumpError::Inexact));
+    assert!(matches!(
+        retarget(&parsed, &LuaHeader { lua_version: 0x52, ..header }),
+        Err(DumpError::UnsupportedVersion { version: 0x52 })
+    ));
+}
+
+#[test]
+fn test_parse_error() {
+    use luac_parser::{LuaBytecode, LuaChunk, ParseError};
+
+    let data = std::fs::read("tests/lua51/concat-duble.luac").unwrap();
+    let err = luac_parser::parse(&data[..data.len() - 2]).unwrap_err();
+    println!("{err}");
+    assert!(matches!(err, ParseError::Truncated { .. }));
+    // the upvalue name count of the main chunk is the last int
+    assert_eq!(err.offset(), data.len() - 4);
+    assert_eq!(err.context(), ["chunk", "count upval names", "integer"]);
+
+    let mut bad = data.clone();
+    bad[4] = 0x50;
+    assert_eq!(
+        luac_parser::parse(&bad).unwrap_err(),
+        ParseError::UnsupportedVersion {
+            offset: 4,
+            version: 0x50
+        }
+    );
+
+    // the tag precedes the size_t length of the string
+    let pos = data.windows(5).position(|w| w == b"print").unwrap() - 9;
+    let mut bad = data.clone();
+    bad[pos] = 9;
+    assert_eq!(
+        luac_parser::parse(&bad).unwrap_err(),
+        ParseError::InvalidConstant {
+            offset: pos,
+            tag: 9,
+            context: vec!["chunk", "count constants"],
+            path: vec![],
+        }
+    );
+
+    let bytecode = LuaBytecode {
+        header: luac_parser::parse(&data).unwrap().header,
+        main_chunk: LuaChunk {
+            prototypes: vec![
+                LuaChunk::default(),
+                LuaChunk {
+                    constants: vec!["child".into()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        },
+    };
+    let mut bad = bytecode.dump().unwrap();
+    let pos = bad.windows(5).position(|w| w == b"child").unwrap() - 9;
+    bad[pos] = 0xff;
+    let err = luac_parser::parse(&bad).unwrap_err();
+    println!("{err}");
+    assert_eq!(err.path(), [1]);
+    assert_eq!(err.offset(), pos);
+    assert_eq!(
+        err.context(),
+        ["chunk", "count prototypes", "chunk", "count constants"]
+    );
+}
+
+#[test]
+fn test_limits() {
+    use luac_parser::{parse_with, Limit, LuaBytecode, LuaChunk, ParseError, ParseOptions};
+
+    let data = std::fs::read("tests/lua51/concat-duble.luac").unwrap();
+    let limit = |options: ParseOptions| match parse_with(&data, &options) {
+        Err(ParseError::LimitExceeded { limit, .. }) => Some(limit),
+        Ok(_) => None,
+        Err(e) => panic!("{e}"),
+    };
+    assert_eq!(limit(ParseOptions::default()), None);
+    let options = ParseOptions {
+        max_count: 8,
+        ..Default::default()
+    };
+    assert_eq!(limit(options), Some(Limit::Count));
+    let options = ParseOptions {
+        max_string_len: 8,
+        ..Default::default()
+    };
+    assert_eq!(limit(options), Some(Limit::StringLength));
+    let options = ParseOptions {
+        max_alloc: 256,
+        ..Default::default()
+    };
+    assert_eq!(limit(options), Some(Limit::Allocation));
+
+    let mut chunk = LuaChunk::default();
+    for _ in 0..4 {
+        chunk = LuaChunk {
+            prototypes: vec![chunk],
+            ..Default::default()
+        };
+    }
+    let bytecode = LuaBytecode {
+        header: luac_parser::parse(&data).unwrap().header,
+        main_chunk: chunk,
+    };
+    let data = bytecode.dump().unwrap();
+    let options = ParseOptions {
+        max_depth: 3,
+        ..Default::default()
+    };
+    let err = parse_with(&data, &options).unwrap_err();
+    assert!(matches!(
+        err,
+        ParseError::LimitExceeded {
+            limit: Limit::Depth,
+            value: 4,
+            ..
+        }
+    ));
+    assert_eq!(err.path(), [0, 0, 0]);
+    assert!(parse_with(&data, &ParseOptions { max_depth: 5, ..options }).is_ok());
+}
@@ -0,0 +1,67 @@
+use luac_parser::{
+    lua52::{decode, Instruction, OpCode, RK},
+    DecodeError, LuaChunk,
+};
+
+#[test]
+fn test_decode() {
+    let chunk = LuaChunk {
+        instructions: vec![
+            2 | 1 << 6,                         // LOADKX 1
+            39 | 300 << 6,                      // EXTRAARG 300
+            11 | 2 << 6,                        // NEWTABLE 2 0 0
+            36 | 2 << 6 | 3 << 23,              // SETLIST 2 3 0
+            39 | 70000 << 6,                    // EXTRAARG 70000
+            16 | 3 << 6 | 257 << 14 | 1 << 23, // DIV 3 1 -2
+            31 | 1 << 23,                       // RETURN 0 1
+        ],
+        ..Default::default()
+    };
+    let code = decode(&chunk).unwrap();
+    println!("{code:#?}");
+
+    assert_eq!(
+        code.iter().map(|(pc, _)| *pc).collect::<Vec<_>>(),
+        [0, 2, 3, 5, 6]
+    );
+    assert_eq!(code[0].1, Instruction::LoadKX { a: 1, ax: 300 });
+    assert_eq!(
+        code[2].1,
+        Instruction::SetList {
+            a: 2,
+            b: 3,
+            c: 70000
+        }
+    );
+    assert_eq!(
+        code[3].1,
+        Instruction::Div {
+            a: 3,
+            b: RK::Register(1),
+            c: RK::Constant(1)
+        }
+    );
+    assert_eq!(code[4].1.opcode(), OpCode::Return);
+    assert_eq!(
+        Instruction::decode(39 | ((1 << 26) - 1) << 6),
+        Some(Instruction::ExtraArg { ax: (1 << 26) - 1 })
+    );
+
+    // LOADKX must be followed by EXTRAARG
+    let chunk = LuaChunk {
+        instructions: vec![2 | 1 << 6, 31 | 1 << 23],
+        ..Default::default()
+    };
+    assert!(matches!(
+        decode(&chunk),
+        Err(DecodeError::InvalidOpcode { pc: 1, .. })
+    ));
+    let chunk = LuaChunk {
+        instructions: vec![36 | 2 << 6 | 3 << 23],
+        ..Default::default()
+    };
+    assert!(matches!(
+        decode(&chunk),
+        Err(DecodeError::Truncated { pc: 0 })
+    ));
+}
@@ -0,0 +1,89 @@
+use luac_parser::{
+    lua53::{decode, Instruction, OpCode, RK},
+    DecodeError, LuaChunk,
+};
+
+#[test]
+fn test_decode() {
+    let chunk = LuaChunk {
+        instructions: vec![
+            2 | 1 << 6,                         // LOADKX 1
+            46 | 300 << 6,                      // EXTRAARG 300
+            11 | 2 << 6,                        // NEWTABLE 2 0 0
+            43 | 2 << 6 | 3 << 23,              // SETLIST 2 3 0
+            46 | 70000 << 6,                    // EXTRAARG 70000
+            16 | 3 << 6 | 257 << 14 | 1 << 23, // MOD 3 1 -2
+            19 | 3 << 6 | 2 << 14 | 1 << 23,   // IDIV 3 1 2
+            20 | 3 << 6 | 2 << 14 | 1 << 23,   // BAND 3 1 2
+            23 | 3 << 6 | 256 << 14 | 1 << 23, // SHL 3 1 -1
+            26 | 4 << 6 | 3 << 23,              // BNOT 4 3
+            38 | 1 << 23,                       // RETURN 0 1
+        ],
+        ..Default::default()
+    };
+    let code = decode(&chunk).unwrap();
+    println!("{code:#?}");
+
+    assert_eq!(
+        code.iter().map(|(pc, _)| *pc).collect::<Vec<_>>(),
+        [0, 2, 3, 5, 6, 7, 8, 9, 10]
+    );
+    assert_eq!(code[0].1, Instruction::LoadKX { a: 1, ax: 300 });
+    assert_eq!(
+        code[2].1,
+        Instruction::SetList {
+            a: 2,
+            b: 3,
+            c: 70000
+        }
+    );
+    assert_eq!(
+        code[3].1,
+        Instruction::Mod {
+            a: 3,
+            b: RK::Register(1),
+            c: RK::Constant(1)
+        }
+    );
+    assert_eq!(
+        code[4].1,
+        Instruction::IDiv {
+            a: 3,
+            b: RK::Register(1),
+            c: RK::Register(2)
+        }
+    );
+    assert_eq!(code[5].1.opcode(), OpCode::BAnd);
+    assert_eq!(
+        code[6].1,
+        Instruction::Shl {
+            a: 3,
+            b: RK::Register(1),
+            c: RK::Constant(0)
+        }
+    );
+    assert_eq!(code[7].1, Instruction::BNot { a: 4, b: 3 });
+    assert_eq!(code[8].1.opcode(), OpCode::Return);
+    assert_eq!(
+        Instruction::decode(46 | ((1 << 26) - 1) << 6),
+        Some(Instruction::ExtraArg { ax: (1 << 26) - 1 })
+    );
+
+    // SETLIST with C == 0 must be followed by EXTRAARG
+    let chunk = LuaChunk {
+        instructions: vec![43 | 2 << 6 | 3 << 23, 38 | 1 << 23],
+        ..Default::default()
+    };
+    assert!(matches!(
+        decode(&chunk),
+        Err(DecodeError::InvalidOpcode { pc: 1, .. })
+    ));
+    let chunk = LuaChunk {
+        instructions: vec![2 | 1 << 6],
+        ..Default::default()
+    };
+    assert!(matches!(
+        decode(&chunk),
+        Err(DecodeError::Truncated { pc: 0 })
+    ));
+}
@@ -0,0 +1,177 @@
+use luac_parser::{
+    lua54::{decode, paired_mmbin, Instruction, OpCode, RK},
+    LuaBytecode, LuaChunk, LuaConstant, LuaHeader, LuaLocal, LuaNumber, LuaVarArgInfo, UpVal,
+};
+
+#[test]
+fn test_decode() {
+    let chunk = LuaChunk {
+        instructions: vec![
+            1 | 1 << 7 | (65535 - 5) << 15,    // LOADI 1 -5
+            19 | 2 << 7 | 1 << 15 | 3 << 24,   // NEWTABLE 2 0 3 k
+            82 | 1 << 7,                       // EXTRAARG 1
+            21 | 1 << 16 | 124 << 24,          // ADDI 0 1 -3
+            47 | 1 << 7 | 124 << 16 | 6 << 24, // MMBINI 1 -3 6 0
+            18 | 2 << 7 | 1 << 15 | 4 << 24,   // SETFIELD 2 0 4k
+            74 | 3 << 15,                      // FORPREP 0 3
+            56 | (16777215 - 2) << 7,          // JMP -2
+            71,                                // RETURN0
+        ],
+        ..Default::default()
+    };
+    let code = decode(&chunk).unwrap();
+    println!("{code:#?}");
+
+    assert_eq!(code[0], (0, Instruction::LoadI { a: 1, sbx: -5 }));
+    assert_eq!(
+        code[1],
+        (
+            1,
+            Instruction::NewTable {
+                a: 2,
+                b: 0,
+                c: 259,
+                k: true
+            }
+        )
+    );
+    assert_eq!(code[2], (3, Instruction::AddI { a: 0, b: 1, sc: -3 }));
+    assert_eq!(
+        paired_mmbin(&code, 2).unwrap().1.metamethod(),
+        Some("__add")
+    );
+    assert_eq!(
+        code[4].1,
+        Instruction::SetField {
+            a: 2,
+            b: 0,
+            c: RK::Constant(4)
+        }
+    );
+    assert_eq!(code[5].1.jump_target(code[5].0), Some(11));
+    assert_eq!(code[6].1.jump_target(code[6].0), Some(6));
+    assert_eq!(code[7].1.opcode(), OpCode::Return0);
+}
+
+#[test]
+fn test_listing() {
+    let bytecode = LuaBytecode {
+        header: LuaHeader {
+            lua_version: 0x54,
+            ..Default::default()
+        },
+        main_chunk: LuaChunk {
+            name: b"@test.lua".to_vec(),
+            max_stack: 2,
+            num_upvalues: 1,
+            is_vararg: Some(LuaVarArgInfo::new()),
+            instructions: vec![
+                81,                          // VARARGPREP 0
+                11,                          // GETTABUP 0 0 0
+                3 | 1 << 7 | 1 << 15,        // LOADK 1 1
+                68 | 2 << 16 | 1 << 24,      // CALL 0 2 1
+                70 | 1 << 16 | 1 << 24,      // RETURN 0 1 1
+            ],
+            constants: vec!["print".into(), "hi".into()],
+            line_info: vec![0, 1, 0, -128, 0],
+            source_lines: vec![(3, 5)],
+            upvalue_infos: vec![UpVal {
+                on_stack: true,
+                id: 0,
+                kind: 0,
+            }],
+            upvalue_names: vec![b"_ENV".to_vec()],
+            ..Default::default()
+        },
+    };
+    let listing = luac_parser::listing::luac(&bytecode, true).unwrap();
+    let listing = listing.replace(&format!("{:p}", &bytecode.main_chunk), "ADDR");
+    print!("{listing}");
+
+    assert_eq!(
+        listing,
+        "
+main <test.lua:0,0> (5 instructions at ADDR)
+0+ params, 2 slots, 1 upvalue, 0 locals, 2 constants, 0 functions
+\t1\t[-]\tVARARGPREP\t0
+\t2\t[1]\tGETTABUP \t0 0 0\t; _ENV \"print\"
+\t3\t[1]\tLOADK    \t1 1\t; \"hi\"
+\t4\t[5]\tCALL     \t0 2 1\t; 1 in 0 out
+\t5\t[5]\tRETURN   \t0 1 1\t; 0 out
+constants (2) for ADDR:
+\t0\tS\t\"print\"
+\t1\tS\t\"hi\"
+locals (0) for ADDR:
+upvalues (1) for ADDR:
+\t0\t_ENV\t1\t0
+"
+    );
+}
+
+#[test]
+fn test_dump() {
+    // lua52 and lua53 share the layout of the chunk tree, so they are covered here as well
+    for version in [0x52, 0x53, 0x54] {
+        let bytecode = LuaBytecode {
+            header: LuaHeader {
+                lua_version: version,
+                int_size: 4,
+                size_t_size: 8,
+                instruction_size: 4,
+                number_size: 8,
+                ..Default::default()
+            },
+            main_chunk: LuaChunk {
+                name: b"@test.lua".to_vec(),
+                max_stack: 2,
+                is_vararg: Some(LuaVarArgInfo::new()),
+                instructions: vec![81, 70 | 1 << 16 | 1 << 24],
+                constants: vec![
+                    LuaConstant::Null,
+                    LuaConstant::Bool(true),
+                    "".into(),
+                    LuaConstant::Number(LuaNumber::Float(1.5)),
+                    "short".into(),
+                    "long".repeat(100).into(),
+                ],
+                upvalue_infos: vec![UpVal {
+                    on_stack: true,
+                    id: 0,
+                    kind: 0,
+                }],
+                upvalue_names: vec![b"_ENV".to_vec()],
+                prototypes: vec![LuaChunk {
+                    line_defined: 1,
+                    last_line_defined: 3,
+                    num_params: 1,
+                    instructions: vec![71],
+                    locals: vec![LuaLocal {
+                        name: "x".into(),
+                        start_pc: 0,
+                        end_pc: 1,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        };
+        let data = bytecode.dump().unwrap();
+        let parsed = luac_parser::parse(&data).unwrap();
+        assert_eq!(parsed.dump().unwrap(), data);
+
+        let chunk = &parsed.main_chunk;
+        assert_eq!(chunk.instructions, bytecode.main_chunk.instructions);
+        assert_eq!(chunk.constants[5].as_literal_str(), Some(&*"long".repeat(100)));
+
+        // an empty string constant has size 1, size 0 is the NULL of an absent source name
+        let empty = match version {
+            0x52 => [&[1, 1, 4][..], &1u64.to_le_bytes(), &[0, 3]].concat(),
+            0x53 => vec![1, 1, 4, 1, 3],
+            _ => vec![0x11, 4, 0x81, 0x13],
+        };
+        assert!(data.windows(empty.len()).any(|w| w == empty));
+        assert_eq!(chunk.prototypes[0].locals[0].name, "x");
+        assert_eq!(chunk.upvalue_names, [b"_ENV"]);
+    }
+}
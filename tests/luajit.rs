@@ -36,3 +36,266 @@ fn test() {
         }
     }
 }
+
+#[test]
+fn test_decode() {
+    use luac_parser::luajit::{decode, OpCode, OperandMode};
+
+    let bc = luac_parser::parse(&std::fs::read("tests/luajit/float.luac").unwrap()).unwrap();
+    let chunk = &bc.main_chunk;
+    let code = decode(&bc.header, chunk).unwrap();
+
+    assert_eq!(code[0].1.op, OpCode::GGet);
+    assert_eq!(code[0].1.gc_constant(chunk).unwrap().as_literal_str(), Some("print"));
+    assert_eq!(code[1].1.op, OpCode::KShort);
+    assert_eq!(code[1].1.d, 160 * 160);
+    assert_eq!(code[2].1.op, OpCode::Call);
+    assert_eq!((code[2].1.b(), code[2].1.c()), (1, 2));
+    assert_eq!(code[10].1.num_constant(chunk), Some(&LuaNumber::Integer(36100)));
+    assert_eq!(code[23].1.op, OpCode::MulVN);
+    assert_eq!(code[23].1.op.cd_mode(), OperandMode::Num);
+    assert_eq!(code[23].1.num_constant(chunk), Some(&LuaNumber::Integer(-150)));
+    assert_eq!(code.last().unwrap().1.op, OpCode::Ret0);
+    assert_eq!(OpCode::TGetR.to_u8(&bc.header), Some(59));
+}
+
+#[test]
+fn test_listing() {
+    let bc = luac_parser::parse(&std::fs::read("tests/luajit/float.luac").unwrap()).unwrap();
+    let listing = luac_parser::listing::luajit(&bc).unwrap();
+    print!("{listing}");
+
+    let lines = listing.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "-- BYTECODE -- float.lua:0-12");
+    assert_eq!(lines[1], "0001    GGET     0   0      ; \"print\"");
+    assert_eq!(lines[2], "0002    KSHORT   1 25600");
+    assert_eq!(lines[3], "0003    CALL     0   1   2");
+    assert_eq!(lines[20], "0020    KNUM     1   3      ; 111122223333.44");
+    assert_eq!(lines[24], "0024    MULVN    1   1   4  ; -150");
+    assert_eq!(lines[29], "0029    RET0     0   1");
+
+    let bc = luac_parser::parse(&std::fs::read("tests/luajit/string.luac").unwrap()).unwrap();
+    let listing = luac_parser::listing::luajit(&bc).unwrap();
+    assert!(listing.contains(&format!("; \"{}\"~\n", "A".repeat(40))));
+
+    // escapes do not count towards the 40 characters
+    let mut bc = luac_parser::parse(&std::fs::read("tests/luajit/float.luac").unwrap()).unwrap();
+    let s = format!("{}\n\x01", "B".repeat(38));
+    for k in bc.main_chunk.constants.iter_mut() {
+        if *k == "print".into() {
+            *k = s.as_str().into();
+        }
+    }
+    let listing = luac_parser::listing::luajit(&bc).unwrap();
+    assert!(listing.contains(&format!("; \"{}\\n\\001\"\n", "B".repeat(38))));
+}
+
+#[test]
+fn test_dump() {
+    // stripped dumps carry no debug info, so they round trip byte by byte
+    let data = std::fs::read("tests/luajit/string.luac").unwrap();
+    let bc = luac_parser::parse(&data).unwrap();
+    assert_eq!(bc.dump().unwrap(), data);
+
+    let bc = luac_parser::parse(&std::fs::read("tests/luajit/float.luac").unwrap()).unwrap();
+    let data = bc.dump().unwrap();
+    let bc2 = luac_parser::parse(&data).unwrap();
+    assert_eq!(bc2.dump().unwrap(), data);
+    assert_eq!(bc2.header.lj_flags, bc.header.lj_flags);
+
+    let (chunk, chunk2) = (&bc.main_chunk, &bc2.main_chunk);
+    assert_eq!(chunk2.name, chunk.name);
+    assert_eq!(chunk2.instructions, chunk.instructions);
+    assert_eq!(chunk2.num_constants, chunk.num_constants);
+    assert_eq!(chunk2.constants, chunk.constants);
+    assert_eq!(chunk2.last_line_defined, chunk.last_line_defined);
+    assert_eq!(chunk2.source_lines, chunk.source_lines);
+}
+
+#[test]
+fn test_debuginfo() {
+    use luac_parser::{LuaLocal, UpVal};
+
+    let data = std::fs::read("tests/luajit/float.luac").unwrap();
+    let mut bc = luac_parser::parse(&data).unwrap();
+    assert_eq!(bc.dump().unwrap(), data);
+
+    let chunk = &mut bc.main_chunk;
+    let lines = chunk.source_lines.iter().map(|l| l.0).collect::<Vec<_>>();
+    assert_eq!(lines[..8], [1, 1, 1, 2, 2, 2, 3, 3]);
+    assert_eq!(chunk.last_line_defined, 12);
+
+    // lines beyond 255 need 2 bytes
+    chunk.source_lines.last_mut().unwrap().0 = 300;
+    chunk.num_upvalues = 1;
+    chunk.upvalue_infos = vec![UpVal {
+        on_stack: true,
+        id: 0,
+        kind: 0,
+    }];
+    chunk.upvalue_names = vec![b"up".to_vec()];
+    let local = |name: &str, start_pc, end_pc| LuaLocal {
+        name: name.into(),
+        start_pc,
+        end_pc,
+        reg: 0,
+    };
+    chunk.locals = vec![
+        local("x", 1, 9),
+        local("(for index)", 2, 5),
+        local("(for limit)", 2, 5),
+        local("y", 4, 5),
+    ];
+    let data = bc.dump().unwrap();
+    // the builtin names are coded as a byte
+    assert!(!data.windows(5).any(|w| w == b"(for "));
+
+    let bc2 = luac_parser::parse(&data).unwrap();
+    let chunk2 = &bc2.main_chunk;
+    assert_eq!(chunk2.source_lines, bc.main_chunk.source_lines);
+    assert_eq!(chunk2.last_line_defined, 300);
+    assert_eq!(chunk2.upvalue_names, bc.main_chunk.upvalue_names);
+    let locals = chunk2
+        .locals
+        .iter()
+        .map(|l| (l.name.as_str(), l.start_pc, l.end_pc))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        locals,
+        [
+            ("x", 1, 9),
+            ("(for index)", 2, 5),
+            ("(for limit)", 2, 5),
+            ("y", 4, 5)
+        ]
+    );
+    assert_eq!(bc2.dump().unwrap(), data);
+}
+
+#[test]
+fn test_malformed() {
+    let data = std::fs::read("tests/luajit/string.luac").unwrap();
+    for len in 0..data.len() {
+        assert!(luac_parser::parse(&data[..len]).is_err());
+    }
+    for i in 0..data.len() {
+        for b in [0, 4, 0x7f, 0xff] {
+            let mut bad = data.clone();
+            bad[i] = b;
+            let _ = luac_parser::parse(&bad);
+        }
+    }
+}
+
+#[test]
+fn test_cdata() {
+    use luac_parser::{LuaConstant, LuaNumber};
+
+    let data = std::fs::read("tests/luajit/string.luac").unwrap();
+    let mut bc = luac_parser::parse(&data).unwrap();
+    let constants = [
+        LuaConstant::Int64(-2),
+        LuaConstant::UInt64(u64::MAX),
+        LuaConstant::Complex { re: 0.0, im: 1.5 },
+        LuaConstant::Complex { re: 2.0, im: -1.0 },
+    ];
+    bc.main_chunk.constants.extend(constants.iter().cloned());
+    let data = bc.dump().unwrap();
+    let bc2 = luac_parser::parse(&data).unwrap();
+    assert_eq!(bc2.main_chunk.constants, bc.main_chunk.constants);
+    assert_eq!(bc2.dump().unwrap(), data);
+
+    let literals = constants.iter().map(|k| k.to_literal()).collect::<Vec<_>>();
+    assert_eq!(literals, ["-2LL", "18446744073709551615ULL", "1.5i", "(2-1i)"]);
+    assert_ne!(constants[0], LuaConstant::Number(LuaNumber::Integer(-2)));
+}
+
+#[test]
+fn test_table() {
+    use luac_parser::{ConstTable, LuaConstant, LuaNumber};
+
+    let int = |i| LuaConstant::Number(LuaNumber::Integer(i));
+    let float = |f| LuaConstant::Number(LuaNumber::Float(f));
+    let data = std::fs::read("tests/luajit/string.luac").unwrap();
+    let mut bc = luac_parser::parse(&data).unwrap();
+    let table = ConstTable {
+        array: vec![LuaConstant::Null, int(-1), "a".into()],
+        hash: vec![
+            (int(-5), int(i32::MIN as _)),
+            (float(0.5), LuaConstant::Bool(true)),
+            ("k".into(), float(-2.25)),
+        ],
+    };
+    bc.main_chunk
+        .constants
+        .push(LuaConstant::Table(Box::new(table.clone())));
+    let data = bc.dump().unwrap();
+    let bc2 = luac_parser::parse(&data).unwrap();
+    let Some(LuaConstant::Table(t)) = bc2.main_chunk.constants.last() else {
+        panic!("{:?}", bc2.main_chunk.constants);
+    };
+    assert_eq!(**t, table);
+
+    // t[0] is nil, so the array part starts at t[1]
+    let pairs = t.pairs().collect::<Vec<_>>();
+    assert_eq!(pairs.len(), 5);
+    assert_eq!(pairs[0], (int(1), &int(-1)));
+    assert_eq!(pairs[1], (int(2), &"a".into()));
+    assert_eq!(pairs[2], (int(-5), &int(i32::MIN as _)));
+
+    let t = ConstTable {
+        array: vec![int(0), int(1), LuaConstant::Null, int(3)],
+        hash: vec![],
+    };
+    let pairs = t.pairs().collect::<Vec<_>>();
+    assert_eq!(pairs, [(int(0), &int(0)), (int(1), &int(1)), (int(3), &int(3))]);
+}
+
+#[test]
+fn test_limits() {
+    use luac_parser::{parse_with, Limit, LuaBytecode, LuaChunk, ParseError, ParseOptions};
+
+    let data = std::fs::read("tests/luajit/string.luac").unwrap();
+    let options = ParseOptions {
+        max_string_len: 512,
+        ..Default::default()
+    };
+    assert!(matches!(
+        parse_with(&data, &options),
+        Err(ParseError::LimitExceeded {
+            limit: Limit::StringLength,
+            value: 766,
+            ..
+        })
+    ));
+
+    // the prototypes are flat in the dump, so the depth is checked on the built tree
+    let header = luac_parser::parse(&data).unwrap().header;
+    let mut chunk = LuaChunk::default();
+    for _ in 0..4 {
+        chunk = LuaChunk {
+            constants: vec![LuaConstant::Proto(0)],
+            prototypes: vec![chunk],
+            ..Default::default()
+        };
+    }
+    let data = LuaBytecode {
+        header,
+        main_chunk: chunk,
+    }
+    .dump()
+    .unwrap();
+    let options = ParseOptions {
+        max_depth: 4,
+        ..Default::default()
+    };
+    assert!(matches!(
+        parse_with(&data, &options),
+        Err(ParseError::LimitExceeded {
+            limit: Limit::Depth,
+            value: 5,
+            ..
+        })
+    ));
+    assert!(parse_with(&data, &ParseOptions { max_depth: 5, ..options }).is_ok());
+}
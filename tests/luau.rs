@@ -23,3 +23,471 @@ fn test() {
         luac_parser::luau::bytecode(&compile(p).unwrap()).unwrap();
     }
 }
+
+#[test]
+fn test_decode() {
+    use luac_parser::instruction::{AnyInstruction, Instruction};
+    use luac_parser::luau::{Instructions, OpCode};
+
+    let code = [
+        12 | 1 << 16,           // GETIMPORT 0 1
+        0x40000000,             // aux
+        4 | 1 << 8 | 5 << 16,   // LOADN 1 5
+        21 | 2 << 16 | 1 << 24, // CALL 0 2 1
+        23 | 0xfffb << 16,      // JUMP -5
+        22 | 1 << 16,           // RETURN 0 1
+    ];
+    let insns = Instructions::new(&code, 6)
+        .map(|i| i.unwrap())
+        .collect::<Vec<_>>();
+    println!("{insns:#?}");
+
+    let pcs = insns.iter().map(|(pc, _)| *pc).collect::<Vec<_>>();
+    assert_eq!(pcs, [0, 2, 3, 4, 5]);
+    assert_eq!(insns[0].1.op, OpCode::GetImport);
+    assert_eq!(insns[0].1.aux, Some(0x40000000));
+    assert_eq!(insns[1].1.d(), 5);
+    assert_eq!(insns[3].1.jump_target(insns[3].0), Some(0));
+    assert!(Instructions::new(&code[..1], 6).next().unwrap().is_err());
+
+    // LOADB skips C instructions unconditionally
+    let code = [
+        3 | 1 << 8 | 1 << 16 | 1 << 24, // LOADB 1 1 1
+        3 | 1 << 8,                     // LOADB 1 0 0
+    ];
+    let insns = Instructions::new(&code, 6)
+        .map(|i| AnyInstruction::Luau(i.unwrap().1))
+        .collect::<Vec<_>>();
+    assert_eq!(insns[0].jump_targets(0), [2]);
+    assert!(!insns[0].falls_through());
+    assert!(insns[1].jump_targets(1).is_empty());
+    assert!(insns[1].falls_through());
+}
+
+#[test]
+fn test_dump() {
+    use luac_parser::{luau, ConstTable, LuaChunk, LuaConstant, LuaLocal, LuaNumber, LuaVarArgInfo};
+
+    let child = LuaChunk {
+        name: b"f".to_vec(),
+        line_defined: 2,
+        num_upvalues: 1,
+        max_stack: 2,
+        instructions: vec![
+            9,                     // GETUPVAL 0 0
+            22 | 1 << 16,          // RETURN 0 1
+        ],
+        constants: vec!["print".into()],
+        upvalue_names: vec![b"print".to_vec()],
+        ..Default::default()
+    };
+    let main = LuaChunk {
+        max_stack: 2,
+        is_vararg: Some(LuaVarArgInfo::new()),
+        instructions: vec![
+            65,                    // PREPVARARGS 0
+            64 | 1 << 16,          // DUPCLOSURE 0 1
+            22 | 1 << 16,          // RETURN 0 1
+        ],
+        constants: vec![
+            "print".into(),
+            LuaConstant::Proto(0),
+            LuaConstant::Number(LuaNumber::Float(1.5)),
+            LuaConstant::Table(Box::new(ConstTable {
+                array: vec![],
+                hash: vec![("print".into(), LuaConstant::Null)],
+            })),
+            LuaConstant::Bool(true),
+            "".into(),
+        ],
+        prototypes: vec![child],
+        locals: vec![LuaLocal {
+            name: "f".into(),
+            start_pc: 2,
+            end_pc: 3,
+            reg: 0,
+        }],
+        ..Default::default()
+    };
+
+    for version in luau::LBC_VERSION_MIN..=luau::LBC_VERSION_MAX {
+        let data = luau::dump_bytecode(&main, version, 1).unwrap();
+        // "print" is shared by the constants and the upvalue name
+        assert_eq!(data.windows(5).filter(|w| w == b"print").count(), 1);
+
+        let (_, chunk) = luau::bytecode(&data).unwrap();
+        assert_eq!(luau::dump_bytecode(&chunk, version, 1).unwrap(), data);
+        // the main function is anonymous although "" is interned for the constants
+        assert_eq!(data[data.len() - 10], 0);
+        assert_eq!(chunk.instructions, main.instructions);
+        assert_eq!(chunk.constants, main.constants);
+        assert!(chunk.is_vararg.is_some());
+        assert_eq!(chunk.locals[0].name, "f");
+        assert_eq!(chunk.locals[0].end_pc, 3);
+
+        let f = &chunk.prototypes[0];
+        assert_eq!(f.name, b"f");
+        assert_eq!(f.line_defined, 2);
+        assert_eq!(f.upvalue_names, [b"print"]);
+        assert_eq!(f.instructions, main.prototypes[0].instructions);
+    }
+    assert!(luau::dump_bytecode(&main, 7, 0).is_err());
+}
+
+#[test]
+fn test_malformed() {
+    use luac_parser::{luau, LuaChunk};
+
+    let main = LuaChunk {
+        instructions: vec![22 | 1 << 16],
+        constants: vec!["x".into()],
+        prototypes: vec![LuaChunk {
+            instructions: vec![22 | 1 << 16],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let data = luau::dump_bytecode(&main, 6, 1).unwrap();
+    for len in 0..data.len() {
+        assert!(luau::bytecode(&data[..len]).is_err());
+    }
+    for i in 0..data.len() {
+        for b in 0..=255 {
+            let mut bad = data.clone();
+            bad[i] = b;
+            let _ = luau::bytecode(&bad);
+        }
+    }
+
+    // the child proto is referenced twice
+    let mut bad = data.clone();
+    *bad.last_mut().unwrap() = 0;
+    assert!(luau::bytecode(&bad).is_err());
+}
+
+#[test]
+fn test_limits() {
+    use luac_parser::{luau, with_options, Limit, LuaChunk, ParseOptions};
+
+    let mut chunk = LuaChunk {
+        instructions: vec![22 | 1 << 16],
+        ..Default::default()
+    };
+    for _ in 0..4 {
+        chunk = LuaChunk {
+            instructions: vec![22 | 1 << 16],
+            prototypes: vec![chunk],
+            ..Default::default()
+        };
+    }
+    let data = luau::dump_bytecode(&chunk, 6, 1).unwrap();
+    let options = ParseOptions {
+        max_depth: 4,
+        ..Default::default()
+    };
+    assert!(with_options(&options, || luau::bytecode(&data)).is_err());
+    assert!(luau::bytecode(&data).is_ok());
+
+    // the default depth applies to the parsers called directly
+    let mut deep = LuaChunk::default();
+    for _ in 0..ParseOptions::default().max_depth {
+        deep = LuaChunk {
+            prototypes: vec![deep],
+            ..Default::default()
+        };
+    }
+    let deep = luau::dump_bytecode(&deep, 6, 1).unwrap();
+    let e = luac_parser::parse(&deep).unwrap_err();
+    assert!(matches!(e, luac_parser::ParseError::LimitExceeded { limit: Limit::Depth, .. }));
+    assert!(luau::bytecode(&deep).is_err());
+
+    let options = ParseOptions {
+        max_count: 4,
+        ..Default::default()
+    };
+    assert!(with_options(&options, || luau::bytecode(&data)).is_err());
+}
+
+#[test]
+fn test_detect() {
+    use luac_parser::{instruction, luau, LuaChunk, LUAU};
+
+    let main = LuaChunk {
+        instructions: vec![65, 22 | 1 << 16],
+        constants: vec!["x".into()],
+        ..Default::default()
+    };
+    for (version, types_version) in [(3, 0), (4, 1), (5, 2), (6, 3)] {
+        let data = luau::dump_bytecode(&main, version, types_version).unwrap();
+        let bc = luac_parser::parse(&data).unwrap();
+        assert_eq!(bc.header.version(), LUAU);
+        assert_eq!(bc.header.format_version, version);
+        assert_eq!(bc.header.types_version, types_version);
+        assert_eq!(bc.main_chunk.constants, main.constants);
+        assert_eq!(bc.dump().unwrap(), data);
+
+        let code = instruction::decode(&bc.header, &bc.main_chunk).unwrap();
+        assert_eq!(code.len(), 2);
+    }
+
+    assert!(luac_parser::parse(&[7, 1, 0]).is_err());
+    assert!(luac_parser::parse(&[6, 9, 0]).is_err());
+}
+
+#[test]
+fn test_compile_error() {
+    use luac_parser::{luau, ParseError};
+
+    let data = b"\0:1: Expected identifier when parsing expression, got ')'";
+    let message = ":1: Expected identifier when parsing expression, got ')'".to_string();
+    let expected = ParseError::LuauCompileError { message };
+    assert_eq!(luac_parser::parse(data).unwrap_err(), expected);
+
+    assert!(luau::bytecode(data).is_err());
+    assert_eq!(
+        luau::compile_error_message(data),
+        Some(":1: Expected identifier when parsing expression, got ')'")
+    );
+    assert!(luac_parser::parse(b"\0").is_err());
+    assert!(luac_parser::parse(b"\0\xff\xfe").is_err());
+    assert_eq!(luau::compile_error_message(b"\0"), None);
+    assert_eq!(luau::compile_error_message(b"\0\xff\xfe"), None);
+    assert_eq!(luau::compile_error_message(b"\x06\x03"), None);
+}
+
+#[test]
+fn test_import() {
+    use luac_parser::{luau, LuaChunk, LuaConstant};
+
+    let id = 2 << 30 | 1 << 10;
+    let main = LuaChunk {
+        constants: vec![
+            "math".into(),
+            "floor".into(),
+            LuaConstant::Import {
+                id,
+                path: vec![],
+            },
+        ],
+        ..Default::default()
+    };
+    let data = luau::dump_bytecode(&main, 3, 0).unwrap();
+    let (_, chunk) = luau::bytecode(&data).unwrap();
+    let LuaConstant::Import { id: id2, path } = &chunk.constants[2] else {
+        panic!("{:?}", chunk.constants[2]);
+    };
+    assert_eq!(*id2, id);
+    assert_eq!(path.len(), 2);
+    assert_eq!(chunk.constants[2].to_literal(), "math.floor");
+
+    // the indices must refer to string constants
+    let main = LuaChunk {
+        constants: vec![LuaConstant::Import { id, path: vec![] }],
+        ..Default::default()
+    };
+    let data = luau::dump_bytecode(&main, 3, 0).unwrap();
+    assert!(luau::bytecode(&data).is_err());
+}
+
+#[test]
+fn test_constants() {
+    use luac_parser::{luau, ConstTable, LuaChunk, LuaConstant, LuaNumber};
+
+    let main = LuaChunk {
+        constants: vec![
+            "x".into(),
+            LuaConstant::Number(LuaNumber::Integer(-300)),
+            LuaConstant::Vector([1.0, 2.5, -3.0, 0.0]),
+            LuaConstant::Table(Box::new(ConstTable {
+                array: vec![],
+                hash: vec![
+                    ("x".into(), LuaConstant::Number(LuaNumber::Integer(-300))),
+                    (LuaConstant::Number(LuaNumber::Integer(-300)), LuaConstant::Null),
+                ],
+            })),
+            LuaConstant::Number(LuaNumber::Integer(i64::MIN)),
+        ],
+        ..Default::default()
+    };
+    let data = luau::dump_bytecode(&main, 6, 3).unwrap();
+    assert!(data.contains(&luau::LBC_CONSTANT_TABLE_WITH_CONSTANTS));
+    let (_, chunk) = luau::bytecode(&data).unwrap();
+    assert_eq!(chunk.constants, main.constants);
+    assert_eq!(chunk.constants[2].to_literal(), "vector(1, 2.5, -3, 0)");
+    assert_eq!(luau::dump_bytecode(&chunk, 6, 3).unwrap(), data);
+
+    // the value index of a table with constants must be valid
+    let mut data = data;
+    let at = data
+        .windows(2)
+        .position(|w| w == [luau::LBC_CONSTANT_TABLE_WITH_CONSTANTS, 2])
+        .unwrap();
+    data[at + 3..at + 7].copy_from_slice(&7i32.to_le_bytes());
+    assert!(luau::bytecode(&data).is_err());
+}
+
+#[test]
+fn test_lineinfo() {
+    use luac_parser::{luau, LuaChunk};
+
+    let lines = [1, 2, 2, 3, 300, 301, 4, 5, 1000, 1000];
+    let main = LuaChunk {
+        instructions: vec![65; lines.len()],
+        source_lines: lines.iter().map(|&l| (l, 0)).collect(),
+        ..Default::default()
+    };
+    let data = luau::dump_bytecode(&main, 3, 0).unwrap();
+    let (_, chunk) = luau::bytecode(&data).unwrap();
+    assert_eq!(chunk.source_lines, main.source_lines);
+    assert_eq!(chunk.last_line_defined, 1000);
+    assert_eq!(luau::dump_bytecode(&chunk, 3, 0).unwrap(), data);
+
+    // linegaplog2 2, the intervals start at 10 and 20, the offsets wrap from 255 to 0
+    let mut code = vec![3, 0, 1, 0, 0, 0, 0, 5];
+    code.extend(std::iter::repeat_n([65, 0, 0, 0], 5).flatten());
+    code.extend([0, 0, 0, 0, 1, 2, 0, 1, 254, 1, 0]);
+    code.extend(10i32.to_le_bytes());
+    code.extend(10i32.to_le_bytes());
+    code.extend([0, 0]);
+    let (_, chunk) = luau::bytecode(&code).unwrap();
+    let lines = chunk.source_lines.iter().map(|l| l.0).collect::<Vec<_>>();
+    assert_eq!(lines, [10, 11, 265, 10, 20]);
+}
+
+#[test]
+fn test_type_info() {
+    use luac_parser::luau::{self, BytecodeType, ProtoFlags, TypeAnnotation, TypeInfo, TypedLocal};
+    use luac_parser::LuaChunk;
+
+    let number = TypeAnnotation {
+        ty: BytecodeType::Number,
+        optional: false,
+    };
+    let userdata = TypeAnnotation {
+        ty: BytecodeType::TaggedUserData(3),
+        optional: true,
+    };
+    let type_info = TypeInfo {
+        args: vec![number, userdata],
+        upvalues: vec![userdata],
+        locals: vec![TypedLocal {
+            ty: number,
+            reg: 2,
+            start_pc: 1,
+            end_pc: 4,
+        }],
+    };
+    let main = LuaChunk {
+        num_params: 2,
+        instructions: vec![65, 22 | 1 << 16],
+        flags: (ProtoFlags::NATIVE_MODULE | ProtoFlags::NATIVE_FUNCTION).bits(),
+        type_info: Some(type_info.clone()),
+        ..Default::default()
+    };
+
+    let data = luau::dump_bytecode(&main, 6, 3).unwrap();
+    let (_, chunk) = luau::bytecode(&data).unwrap();
+    assert_eq!(chunk.type_info, main.type_info);
+    assert!(chunk.luau_flags().contains(ProtoFlags::NATIVE_FUNCTION));
+    assert_eq!(luau::dump_bytecode(&chunk, 6, 3).unwrap(), data);
+    assert_eq!(u8::from(userdata), 0x80 | 67);
+
+    // only the signature is kept by types version 1
+    let data = luau::dump_bytecode(&main, 4, 1).unwrap();
+    let (_, chunk) = luau::bytecode(&data).unwrap();
+    assert_eq!(chunk.type_info.as_ref().unwrap().args, type_info.args);
+    assert_eq!(luau::dump_bytecode(&chunk, 4, 1).unwrap(), data);
+}
+
+#[test]
+fn test_opcode_encoding() {
+    use luac_parser::luau::{self, Instructions, OpCode, OpcodeEncoding};
+    use luac_parser::{LuaChunk, LuaConstant};
+
+    let encoding = OpcodeEncoding::ROBLOX;
+    assert_eq!(OpcodeEncoding::new(227), Some(encoding));
+    assert_eq!(OpcodeEncoding::new(2), None);
+
+    let code = vec![
+        65,                    // PREPVARARGS 0
+        12 | 2 << 16,          // GETIMPORT 0 2
+        1 << 30 | 12,          // AUX, the low byte looks like GETIMPORT
+        22 | 1 << 16,          // RETURN 0 1
+    ];
+    let encode = |code: &[u32]| {
+        let mut code = code.to_vec();
+        for pc in [0, 1, 3] {
+            code[pc] = code[pc] & !0xff | encoding.encode(code[pc] as u8) as u32;
+        }
+        code
+    };
+    let main = LuaChunk {
+        instructions: encode(&code),
+        constants: vec!["print".into(), LuaConstant::Null, LuaConstant::Import { id: 1 << 30, path: vec![] }],
+        prototypes: vec![LuaChunk {
+            instructions: encode(&code),
+            constants: vec!["print".into(), LuaConstant::Null, LuaConstant::Import { id: 1 << 30, path: vec![] }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let data = luau::dump_bytecode(&main, 5, 1).unwrap();
+
+    let (_, chunk) = luau::bytecode(&data).unwrap();
+    assert_eq!(OpcodeEncoding::detect(&chunk, 5), Some(encoding));
+    let ops = Instructions::new(&chunk.instructions, 5)
+        .with_encoding(encoding)
+        .map(|i| i.unwrap().1.op)
+        .collect::<Vec<_>>();
+    assert_eq!(ops, [OpCode::PrepVarArgs, OpCode::GetImport, OpCode::Return]);
+
+    for encoding in [None, Some(encoding)] {
+        let (_, chunk) = luau::bytecode_with(&data, encoding).unwrap();
+        assert_eq!(chunk.instructions, code);
+        assert_eq!(chunk.prototypes[0].instructions, code);
+    }
+    assert!(luau::bytecode_with(&data, Some(OpcodeEncoding::NONE)).is_err());
+}
+
+#[test]
+fn test_fastcall() {
+    use luac_parser::luau::{Instructions, OpCode};
+
+    let op = |op: OpCode| op.to_u8() as u32;
+    let code = [
+        op(OpCode::FastCall2) | 1 << 8 | 1 << 16 | 3 << 24, // FASTCALL2 1 1 3
+        2,                                                  // aux
+        op(OpCode::Move) | 3 << 8 | 1 << 16,                // MOVE 3 1
+        op(OpCode::Move) | 4 << 8 | 2 << 16,                // MOVE 4 2
+        op(OpCode::Call) | 2 << 8 | 3 << 16 | 2 << 24,      // CALL 2 3 2
+        op(OpCode::FastCall2K) | 1 << 8 | 1 << 16 | 1 << 24, // FASTCALL2K 1 1 1
+        0,                                                  // aux
+        op(OpCode::Call) | 2 << 8 | 3 << 16 | 2 << 24,      // CALL 2 3 2
+        op(OpCode::FastCall1) | 1 << 8 | 1 << 16 | 1 << 24, // FASTCALL1 1 1 1
+        op(OpCode::Move) | 3 << 8 | 1 << 16,                // MOVE 3 1
+        op(OpCode::Call) | 2 << 8 | 2 << 16 | 2 << 24,      // CALL 2 2 2
+        op(OpCode::Return) | 1 << 16,                       // RETURN 0 1
+    ];
+    let insns = Instructions::new(&code, 6)
+        .map(|i| i.unwrap())
+        .collect::<Vec<_>>();
+    // the target is the instruction following the CALL
+    let targets = insns
+        .iter()
+        .filter(|(_, i)| i.op.is_fastcall())
+        .map(|(pc, i)| i.jump_target(*pc))
+        .collect::<Vec<_>>();
+    assert_eq!(targets, [Some(5), Some(8), Some(11)]);
+
+    // the deprecated generic loops of version 5 jump back by D
+    let code = [
+        op(OpCode::Nop),
+        60 | 0xffff << 16, // DEP_FORGLOOP_INEXT 0 -1
+        0,
+    ];
+    let insns = Instructions::new(&code, 5)
+        .map(|i| i.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(insns[1].1.op, OpCode::DepForGLoopINext);
+    assert_eq!(insns[1].1.jump_target(1), Some(1));
+}